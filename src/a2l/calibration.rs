@@ -0,0 +1,152 @@
+use super::debuginfo::{DbgDataType, DebugData, TypeInfo};
+use super::{ifdata, symbol::find_symbol};
+use crate::report::Report;
+use a2lfile::{ConversionType, Module, SymbolLink};
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+
+/// an a2l object that is linked to a variable in the elf file
+#[derive(Debug)]
+pub(crate) struct CalibrationObject {
+    /// the address of the data in the input elf file
+    pub(crate) address: u64,
+    pub(crate) size: u64,
+    /// the type of the values, if the data is a single value or an array of values
+    pub(crate) value_type: Option<ValueType>,
+    /// the lower and upper limit of the internal values
+    pub(crate) limits: Option<(f64, f64)>,
+}
+
+/// how a single value is stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ValueType {
+    Unsigned(u64),
+    Signed(u64),
+    Float(u64),
+}
+
+/// find the data of all CHARACTERISTICs, AXIS_PTS and MEASUREMENTs that have a SYMBOL_LINK.
+/// The limits are converted to internal values, if their conversion can be inverted
+pub(crate) fn calibration_objects(
+    filename_in: &OsStr,
+    filename_elf: &OsStr,
+    split_dwarf: &[OsString],
+    report: &mut Report,
+) -> Result<Vec<CalibrationObject>, String> {
+    let (a2l, _) = a2lfile::load(
+        filename_in,
+        Some(ifdata::A2MLVECTOR_TEXT.to_string()),
+        false,
+    )
+    .map_err(|e| e.to_string())?;
+
+    let debuginfo = DebugData::load_dwarf(filename_elf, split_dwarf).map_err(|e| e.to_string())?;
+    report.add_debug_info_errors(&debuginfo.load_errors);
+
+    let mut objects = vec![];
+    for module in &a2l.project.module {
+        let characteristics = module.characteristic.iter().map(|item| {
+            let limits =
+                internal_limits(module, &item.conversion, item.lower_limit, item.upper_limit);
+            (&item.symbol_link, limits)
+        });
+        let axis_pts = module.axis_pts.iter().map(|item| {
+            let limits =
+                internal_limits(module, &item.conversion, item.lower_limit, item.upper_limit);
+            (&item.symbol_link, limits)
+        });
+        let measurements = module.measurement.iter().map(|item| {
+            let limits =
+                internal_limits(module, &item.conversion, item.lower_limit, item.upper_limit);
+            (&item.symbol_link, limits)
+        });
+        for (symbol_link, limits) in characteristics.chain(axis_pts).chain(measurements) {
+            if let Some(object) = calibration_object(symbol_link.as_ref(), limits, &debuginfo) {
+                objects.push(object);
+            }
+        }
+    }
+
+    Ok(objects)
+}
+
+fn calibration_object(
+    symbol_link: Option<&SymbolLink>,
+    limits: Option<(f64, f64)>,
+    debuginfo: &DebugData,
+) -> Option<CalibrationObject> {
+    let symbol_link = symbol_link?;
+    // the input elf file has not been obfuscated, so the symbol names don't need to be mapped
+    let sym_info = find_symbol(&symbol_link.symbol_name, debuginfo, &HashMap::new()).ok()?;
+    let address = sym_info
+        .address
+        .wrapping_add_signed(i64::from(symbol_link.offset));
+    let typeinfo = sym_info.typeinfo.get_reference(&debuginfo.types);
+
+    Some(CalibrationObject {
+        address,
+        size: typeinfo.get_size(),
+        value_type: value_type(typeinfo, &debuginfo.types),
+        limits,
+    })
+}
+
+// get the type of the values of a variable that is either a single value or an array of values
+fn value_type(typeinfo: &TypeInfo, types: &HashMap<usize, TypeInfo>) -> Option<ValueType> {
+    let mut typeinfo = typeinfo.get_reference(types);
+    if let DbgDataType::Array { arraytype, .. } = &typeinfo.datatype {
+        typeinfo = arraytype.get_reference(types);
+    }
+    match &typeinfo.datatype {
+        DbgDataType::Uint8 => Some(ValueType::Unsigned(1)),
+        DbgDataType::Uint16 => Some(ValueType::Unsigned(2)),
+        DbgDataType::Uint32 => Some(ValueType::Unsigned(4)),
+        DbgDataType::Uint64 => Some(ValueType::Unsigned(8)),
+        DbgDataType::Sint8 => Some(ValueType::Signed(1)),
+        DbgDataType::Sint16 => Some(ValueType::Signed(2)),
+        DbgDataType::Sint32 => Some(ValueType::Signed(4)),
+        DbgDataType::Sint64 => Some(ValueType::Signed(8)),
+        DbgDataType::Float => Some(ValueType::Float(4)),
+        DbgDataType::Double => Some(ValueType::Float(8)),
+        // random values within the limits are unlikely to be valid enumerators
+        _ => None,
+    }
+}
+
+// the limits in the a2l file are physical values. They can only be converted to internal
+// values if the COMPU_METHOD is not a table or a formula
+fn internal_limits(
+    module: &Module,
+    conversion: &str,
+    lower: f64,
+    upper: f64,
+) -> Option<(f64, f64)> {
+    let (lower, upper) = match module.compu_method.get(conversion) {
+        None => (lower, upper),
+        Some(compu_method) => match compu_method.conversion_type {
+            ConversionType::Identical => (lower, upper),
+            ConversionType::Linear => {
+                // PHYS = a * INT + b
+                let coeffs = compu_method.coeffs_linear.as_ref()?;
+                if coeffs.a == 0.0 {
+                    return None;
+                }
+                ((lower - coeffs.b) / coeffs.a, (upper - coeffs.b) / coeffs.a)
+            }
+            ConversionType::RatFunc => {
+                // INT = (a * PHYS^2 + b * PHYS + c) / (d * PHYS^2 + e * PHYS + f)
+                let coeffs = compu_method.coeffs.as_ref()?;
+                // only the linear case is monotonic, so that the limits stay limits
+                if coeffs.a != 0.0 || coeffs.d != 0.0 || coeffs.e != 0.0 || coeffs.f == 0.0 {
+                    return None;
+                }
+                (
+                    (coeffs.b * lower + coeffs.c) / coeffs.f,
+                    (coeffs.b * upper + coeffs.c) / coeffs.f,
+                )
+            }
+            _ => return None,
+        },
+    };
+    (lower.is_finite() && upper.is_finite()).then(|| (lower.min(upper), lower.max(upper)))
+}
@@ -102,27 +102,32 @@ pub(crate) fn get_location_attribute(
     }
 }
 
-// get the address offset of a struct member from a DW_AT_data_member_location attribute
+// get the address offset of a struct member from a DW_AT_data_member_location attribute.
+// An attribute with an unexpected form is an error, while a missing attribute is not
 pub(crate) fn get_data_member_location_attribute(
     debug_data_reader: &DebugDataReader,
     entry: &DebuggingInformationEntry<SliceType, usize>,
     encoding: gimli::Encoding,
     current_unit: usize,
-) -> Option<u64> {
-    let loc_attr = get_attr_value(entry, gimli::constants::DW_AT_data_member_location)?;
+) -> Result<Option<u64>, String> {
+    let Some(loc_attr) = get_attr_value(entry, gimli::constants::DW_AT_data_member_location) else {
+        return Ok(None);
+    };
     match loc_attr {
-        gimli::AttributeValue::Exprloc(expression) => {
-            evaluate_exprloc(debug_data_reader, expression, encoding, current_unit)
-        }
-        gimli::AttributeValue::Udata(val) => Some(val),
-        gimli::AttributeValue::Data1(val) => Some(u64::from(val)),
-        gimli::AttributeValue::Data2(val) => Some(u64::from(val)),
-        gimli::AttributeValue::Data4(val) => Some(u64::from(val)),
-        gimli::AttributeValue::Data8(val) => Some(val),
-        other => {
-            println!("unexpected data_member_location attribute: {other:?}");
-            None
-        }
+        gimli::AttributeValue::Exprloc(expression) => Ok(evaluate_exprloc(
+            debug_data_reader,
+            expression,
+            encoding,
+            current_unit,
+        )),
+        gimli::AttributeValue::Udata(val) => Ok(Some(val)),
+        gimli::AttributeValue::Data1(val) => Ok(Some(u64::from(val))),
+        gimli::AttributeValue::Data2(val) => Ok(Some(u64::from(val))),
+        gimli::AttributeValue::Data4(val) => Ok(Some(u64::from(val))),
+        gimli::AttributeValue::Data8(val) => Ok(Some(val)),
+        other => Err(format!(
+            "unexpected data_member_location attribute: {other:?}"
+        )),
     }
 }
 
@@ -286,43 +291,43 @@ pub(crate) fn get_data_bit_offset_attribute(
     }
 }
 
-pub(crate) fn get_specification_attribute<'data, 'abbrev, 'unit>(
-    entry: &'data DebuggingInformationEntry<SliceType, usize>,
-    unit: &'unit UnitHeader<EndianSlice<'data, RunTimeEndian>>,
-    abbrev: &'abbrev gimli::Abbreviations,
-) -> Option<DebuggingInformationEntry<EndianSlice<'data, RunTimeEndian>, usize>> {
+// get the entry that is referenced by a DW_AT_specification attribute, together with the index of its unit
+pub(crate) fn get_specification_attribute<'data>(
+    entry: &DebuggingInformationEntry<SliceType, usize>,
+    unit_list: &UnitList<'data>,
+    current_unit: usize,
+) -> Option<(DebuggingInformationEntry<SliceType<'data>, usize>, usize)> {
     let specification_attr = get_attr_value(entry, gimli::constants::DW_AT_specification)?;
-    match specification_attr {
-        gimli::AttributeValue::UnitRef(unitoffset) => {
-            if let Ok(specification_entry) = unit.entry(abbrev, unitoffset) {
-                Some(specification_entry)
-            } else {
-                None
-            }
-        }
-        gimli::AttributeValue::DebugInfoRef(_) => {
-            // presumably, a debugger could also generate a DebugInfo ref instead on a UnitRef
-            // parsing this would take info that we don't have here, e.g. the unit headers and abbreviations of all units
-            // fortunately I have not seen a compiler generate this variation yet
-            None
-        }
-        _ => None,
-    }
+    get_referenced_entry(specification_attr, unit_list, current_unit)
 }
 
-pub(crate) fn get_abstract_origin_attribute<'data, 'abbrev, 'unit>(
-    entry: &'data DebuggingInformationEntry<SliceType, usize>,
-    unit: &'unit UnitHeader<EndianSlice<'data, RunTimeEndian>>,
-    abbrev: &'abbrev gimli::Abbreviations,
-) -> Option<DebuggingInformationEntry<EndianSlice<'data, RunTimeEndian>, usize>> {
+// get the entry that is referenced by a DW_AT_abstract_origin attribute, together with the index of its unit
+pub(crate) fn get_abstract_origin_attribute<'data>(
+    entry: &DebuggingInformationEntry<SliceType, usize>,
+    unit_list: &UnitList<'data>,
+    current_unit: usize,
+) -> Option<(DebuggingInformationEntry<SliceType<'data>, usize>, usize)> {
     let origin_attr = get_attr_value(entry, gimli::constants::DW_AT_abstract_origin)?;
-    match origin_attr {
+    get_referenced_entry(origin_attr, unit_list, current_unit)
+}
+
+// a reference is usually a UnitRef inside the current unit. A DebugInfoRef can point into any
+// unit, e.g. to the declaration of a static class member in a type that is shared by several units
+fn get_referenced_entry<'data>(
+    attr_value: gimli::AttributeValue<SliceType>,
+    unit_list: &UnitList<'data>,
+    current_unit: usize,
+) -> Option<(DebuggingInformationEntry<SliceType<'data>, usize>, usize)> {
+    match attr_value {
         gimli::AttributeValue::UnitRef(unitoffset) => {
-            if let Ok(origin_entry) = unit.entry(abbrev, unitoffset) {
-                Some(origin_entry)
-            } else {
-                None
-            }
+            let (unit, abbrev) = &unit_list[current_unit];
+            Some((unit.entry(abbrev, unitoffset).ok()?, current_unit))
+        }
+        gimli::AttributeValue::DebugInfoRef(dbginfo_offset) => {
+            let unit_idx = unit_list.get_unit(dbginfo_offset.0)?;
+            let (unit, abbrev) = &unit_list[unit_idx];
+            let unitoffset = dbginfo_offset.to_unit_offset(unit)?;
+            Some((unit.entry(abbrev, unitoffset).ok()?, unit_idx))
         }
         _ => None,
     }
@@ -372,7 +377,6 @@ fn evaluate_exprloc(
                 let mut entries = unit_header.entries(abbrev);
                 let entry = entries.next_dfs().ok()??;
                 let base = get_addr_base_attribute(entry)?;
-                println!("evaluate_exprloc / base: {base:?}");
                 let addr = debug_data_reader
                     .dwarf
                     .debug_addr
@@ -434,3 +438,15 @@ pub(crate) fn get_declaration_attribute(
         None
     }
 }
+
+// get the DW_AT_external attribute, which marks entities that are visible outside of their compile unit
+pub(crate) fn get_external_attribute(
+    entry: &DebuggingInformationEntry<SliceType, usize>,
+) -> Option<bool> {
+    let external_attr = get_attr_value(entry, gimli::constants::DW_AT_external)?;
+    if let gimli::AttributeValue::Flag(flag) = external_attr {
+        Some(flag)
+    } else {
+        None
+    }
+}
@@ -4,7 +4,8 @@ use gimli::{EndianSlice, RunTimeEndian};
 use indexmap::IndexMap;
 use object::read::ObjectSection;
 use object::{Endianness, Object};
-use std::ffi::OsStr;
+use std::borrow::Cow;
+use std::ffi::{OsStr, OsString};
 use std::ops::Index;
 use std::{collections::HashMap, fs::File};
 
@@ -12,8 +13,8 @@ type SliceType<'a> = EndianSlice<'a, RunTimeEndian>;
 
 mod attributes;
 use attributes::{
-    get_abstract_origin_attribute, get_location_attribute, get_name_attribute,
-    get_specification_attribute, get_typeref_attribute,
+    get_abstract_origin_attribute, get_external_attribute, get_location_attribute,
+    get_name_attribute, get_specification_attribute, get_typeref_attribute,
 };
 mod typereader;
 
@@ -23,7 +24,7 @@ pub(crate) struct UnitList<'a> {
 
 struct DebugDataReader<'elffile> {
     dwarf: Dwarf<EndianSlice<'elffile, RunTimeEndian>>,
-    verbose: bool,
+    load_errors: Vec<String>,
     units: UnitList<'elffile>,
     unit_names: Vec<Option<String>>,
     endian: Endianness,
@@ -31,21 +32,27 @@ struct DebugDataReader<'elffile> {
 }
 
 // load the debug info from an elf file
-pub(crate) fn load_dwarf(filename: &OsStr, verbose: bool) -> Result<DebugData, String> {
+pub(crate) fn load_dwarf(filename: &OsStr, split_dwarf: &[OsString]) -> Result<DebugData, String> {
     let filedata = load_filedata(filename)?;
     let elffile = load_elf_file(&filename.to_string_lossy(), &filedata)?;
 
-    if !elffile
-        .sections()
-        .any(|section| section.name() == Ok(".debug_info"))
-    {
+    // section_by_name also finds the compressed section .zdebug_info
+    if elffile.section_by_name(".debug_info").is_none() {
         return Err(format!(
             "Error: {} does not contain DWARF2+ debug info. The section .debug_info is missing.",
             filename.to_string_lossy()
         ));
     }
 
-    let dwarf = load_dwarf_sections(&elffile)?;
+    let dwarf_sections = load_dwarf_sections(&elffile)?;
+    let endian = get_endian(&elffile);
+    let dwarf = dwarf_sections.borrow(|section| EndianSlice::new(section, endian));
+    // skeleton units are replaced by their split units, in the same way as in the obfuscated debug info
+    let unsplit_dwarf = crate::splitdwarf::unsplit_dwarf(&dwarf, filename, split_dwarf)?;
+    let dwarf = match &unsplit_dwarf {
+        Some(unsplit_dwarf) => unsplit_dwarf.dwarf(),
+        None => dwarf,
+    };
 
     if !verify_dwarf_compile_units(&dwarf) {
         return Err(format!(
@@ -58,7 +65,7 @@ pub(crate) fn load_dwarf(filename: &OsStr, verbose: bool) -> Result<DebugData, S
 
     let dbg_reader = DebugDataReader {
         dwarf,
-        verbose,
+        load_errors: Vec::new(),
         units: UnitList::new(),
         unit_names: Vec::new(),
         endian: elffile.endianness(),
@@ -68,6 +75,40 @@ pub(crate) fn load_dwarf(filename: &OsStr, verbose: bool) -> Result<DebugData, S
     Ok(dbg_reader.read_debug_info_entries())
 }
 
+// load the debug info from DWARF data that is already in memory, e.g. the obfuscated debug info before it is written
+pub(crate) fn load_dwarf_from(dwarf: Dwarf<SliceType>, endian: Endianness) -> DebugData {
+    let dbg_reader = DebugDataReader {
+        dwarf,
+        load_errors: Vec::new(),
+        units: UnitList::new(),
+        unit_names: Vec::new(),
+        endian,
+        sections: HashMap::new(),
+    };
+
+    dbg_reader.read_debug_info_entries()
+}
+
+// load the types at the given offsets from DWARF data that is already in memory, independent of any variables
+pub(crate) fn load_types_from(
+    dwarf: Dwarf<SliceType>,
+    endian: Endianness,
+    type_offsets: &[usize],
+) -> (HashMap<usize, TypeInfo>, Vec<String>) {
+    let mut dbg_reader = DebugDataReader {
+        dwarf,
+        load_errors: Vec::new(),
+        units: UnitList::new(),
+        unit_names: Vec::new(),
+        endian,
+        sections: HashMap::new(),
+    };
+    dbg_reader.load_units();
+
+    let types = dbg_reader.load_types_at(type_offsets);
+    (types, dbg_reader.load_errors)
+}
+
 // open a file and mmap its content
 fn load_filedata(filename: &OsStr) -> Result<memmap2::Mmap, String> {
     let file = match File::open(filename) {
@@ -106,23 +147,25 @@ fn get_elf_sections(elffile: &object::read::File) -> HashMap<String, (u64, u64)>
     for section in elffile.sections() {
         let addr = section.address();
         let size = section.size();
-        if addr != 0 && size != 0 {
-            if let Ok(name) = section.name() {
-                map.insert(name.to_string(), (addr, addr + size));
-            }
+        if addr != 0
+            && size != 0
+            && let Ok(name) = section.name()
+        {
+            map.insert(name.to_string(), (addr, addr + size));
         }
     }
 
     map
 }
 
-// load the DWARF debug info from the .debug_<xyz> sections
+// load the DWARF debug info from the .debug_<xyz> sections.
+// Compressed sections are decompressed, so the section data is only borrowed from the file if it is uncompressed
 fn load_dwarf_sections<'data>(
     elffile: &object::read::File<'data>,
-) -> Result<gimli::Dwarf<SliceType<'data>>, String> {
-    // Dwarf::load takes two closures / functions and uses them to load all the required debug sections
-    let loader = |section: gimli::SectionId| get_file_section_reader(elffile, section.name());
-    gimli::Dwarf::load(loader)
+) -> Result<gimli::DwarfSections<Cow<'data, [u8]>>, String> {
+    // DwarfSections::load takes a closure / function and uses it to load all the required debug sections
+    let loader = |section: gimli::SectionId| get_file_section_data(elffile, section.name());
+    gimli::DwarfSections::load(loader)
 }
 
 // verify that the dwarf data is valid
@@ -136,19 +179,21 @@ fn verify_dwarf_compile_units(dwarf: &gimli::Dwarf<SliceType>) -> bool {
     units_count > 0
 }
 
-// get a section from the elf file.
-// returns a slice referencing the section data if it exists, or an empty slice otherwise
-fn get_file_section_reader<'data>(
+// get the uncompressed data of a section from the elf file. A section .debug_<xyz> can also be stored as .zdebug_<xyz>.
+// returns the section data if it exists, or an empty slice otherwise
+fn get_file_section_data<'data>(
     elffile: &object::read::File<'data>,
     section_name: &str,
-) -> Result<SliceType<'data>, String> {
+) -> Result<Cow<'data, [u8]>, String> {
     if let Some(dbginfo) = elffile.section_by_name(section_name) {
-        match dbginfo.data() {
-            Ok(val) => Ok(EndianSlice::new(val, get_endian(elffile))),
-            Err(e) => Err(e.to_string()),
+        let mut data = dbginfo.uncompressed_data().map_err(|e| e.to_string())?;
+        // in a relocatable object file the addresses and string offsets are only set by the relocations
+        if elffile.kind() == object::ObjectKind::Relocatable {
+            crate::relocate::relocate_section(elffile, &dbginfo, data.to_mut());
         }
+        Ok(data)
     } else {
-        Ok(EndianSlice::new(&[], get_endian(elffile)))
+        Ok(Cow::Borrowed(&[]))
     }
 }
 
@@ -179,18 +224,27 @@ impl DebugDataReader<'_> {
             demangled_names,
             unit_names,
             _sections: self.sections,
+            load_errors: self.load_errors,
         }
     }
 
-    // load all global variables from the dwarf data
-    fn load_variables(&mut self) -> IndexMap<String, Vec<VarInfo>> {
-        let mut variables = IndexMap::<String, Vec<VarInfo>>::new();
-
+    // read the headers and abbreviations of all units
+    fn load_units(&mut self) {
         let mut iter = self.dwarf.debug_info.units();
         while let Ok(Some(unit)) = iter.next() {
             let abbreviations = unit.abbreviations(&self.dwarf.debug_abbrev).unwrap();
             self.units.add(unit, abbreviations);
-            let unit_idx = self.units.list.len() - 1;
+        }
+    }
+
+    // load all global variables from the dwarf data
+    fn load_variables(&mut self) -> IndexMap<String, Vec<VarInfo>> {
+        let mut variables = IndexMap::<String, Vec<VarInfo>>::new();
+
+        // all units must be known in advance, because references can point into later units
+        self.load_units();
+
+        for unit_idx in 0..self.units.list.len() {
             let (unit, abbreviations) = &self.units[unit_idx];
 
             // The root of the tree inside of a unit is always a DW_TAG_compile_unit or DW_TAG_partial_unit.
@@ -198,13 +252,12 @@ impl DebugDataReader<'_> {
             // in functions are declared inside of DW_TAG_subprogram[/DW_TAG_lexical_block]*.
             // We can easily find all of them by using depth-first traversal of the tree
             let mut entries_cursor = unit.entries(abbreviations);
-            if let Ok(Some(entry)) = entries_cursor.next_dfs() {
-                if entry.tag() == gimli::constants::DW_TAG_compile_unit
-                    || entry.tag() == gimli::constants::DW_TAG_partial_unit
-                {
-                    self.unit_names
-                        .push(get_name_attribute(entry, &self.dwarf, unit).ok());
-                }
+            if let Ok(Some(entry)) = entries_cursor.next_dfs()
+                && (entry.tag() == gimli::constants::DW_TAG_compile_unit
+                    || entry.tag() == gimli::constants::DW_TAG_partial_unit)
+            {
+                self.unit_names
+                    .push(get_name_attribute(entry, &self.dwarf, unit).ok());
             }
 
             let mut context: Vec<(gimli::DwTag, Option<String>)> = Vec::new();
@@ -226,29 +279,35 @@ impl DebugDataReader<'_> {
                 debug_assert_eq!(depth as usize, context.len());
 
                 if entry.tag() == gimli::constants::DW_TAG_variable {
-                    match self.get_global_variable(entry, unit, abbreviations) {
-                        Ok(Some((name, typeref, address))) => {
+                    match self.get_global_variable(entry, unit, unit_idx) {
+                        Ok(Some((name, typeref, address, external))) => {
                             let (function, namespaces) = get_varinfo_from_context(&context);
+                            let dbginfo_offset = entry
+                                .offset()
+                                .to_debug_info_offset(unit)
+                                .unwrap_or(gimli::DebugInfoOffset(0))
+                                .0;
                             variables.entry(name).or_default().push(VarInfo {
                                 address,
                                 typeref,
                                 unit_idx,
                                 function,
                                 namespaces,
+                                dbginfo_offset,
+                                external,
                             });
                         }
                         Ok(None) => {
                             // unremarkable, the variable is not a global variable
                         }
                         Err(errmsg) => {
-                            if self.verbose {
-                                let offset = entry
-                                    .offset()
-                                    .to_debug_info_offset(unit)
-                                    .unwrap_or(gimli::DebugInfoOffset(0))
-                                    .0;
-                                println!("Error loading variable @{offset:x}: {errmsg}");
-                            }
+                            let offset = entry
+                                .offset()
+                                .to_debug_info_offset(unit)
+                                .unwrap_or(gimli::DebugInfoOffset(0))
+                                .0;
+                            self.load_errors
+                                .push(format!("Error loading variable @{offset:x}: {errmsg}"));
                         }
                     }
                 }
@@ -264,36 +323,48 @@ impl DebugDataReader<'_> {
         &self,
         entry: &DebuggingInformationEntry<SliceType, usize>,
         unit: &UnitHeader<SliceType>,
-        abbrev: &gimli::Abbreviations,
-    ) -> Result<Option<(String, usize, u64)>, String> {
-        match get_location_attribute(self, entry, unit.encoding(), &self.units.list.len() - 1) {
+        unit_idx: usize,
+    ) -> Result<Option<(String, usize, u64, bool)>, String> {
+        match get_location_attribute(self, entry, unit.encoding(), unit_idx) {
             Some(address) => {
                 // if debugging information entry A has a DW_AT_specification or DW_AT_abstract_origin attribute
                 // pointing to another debugging information entry B, any attributes of B are considered to be part of A.
-                if let Some(specification_entry) = get_specification_attribute(entry, unit, abbrev)
+                if let Some((specification_entry, spec_unit_idx)) =
+                    get_specification_attribute(entry, &self.units, unit_idx)
                 {
                     // the entry refers to a specification, which contains the name and type reference
-                    let name = get_name_attribute(&specification_entry, &self.dwarf, unit)?;
-                    let typeref = get_typeref_attribute(&specification_entry, unit)?;
-
-                    Ok(Some((name, typeref, address)))
-                } else if let Some(abstract_origin_entry) =
-                    get_abstract_origin_attribute(entry, unit, abbrev)
+                    let (spec_unit, _) = &self.units[spec_unit_idx];
+                    let name = get_name_attribute(&specification_entry, &self.dwarf, spec_unit)?;
+                    let typeref = get_typeref_attribute(&specification_entry, spec_unit)?;
+                    // the definition of a variable that was declared elsewhere usually has no
+                    // DW_AT_external of its own; it is taken from the declaration
+                    let external = get_external_attribute(entry)
+                        .or_else(|| get_external_attribute(&specification_entry))
+                        .unwrap_or(false);
+
+                    Ok(Some((name, typeref, address, external)))
+                } else if let Some((abstract_origin_entry, origin_unit_idx)) =
+                    get_abstract_origin_attribute(entry, &self.units, unit_idx)
                 {
                     // the entry refers to an abstract origin, which should also be considered when getting the name and type ref
+                    let (origin_unit, _) = &self.units[origin_unit_idx];
                     let name = get_name_attribute(entry, &self.dwarf, unit).or_else(|_| {
-                        get_name_attribute(&abstract_origin_entry, &self.dwarf, unit)
+                        get_name_attribute(&abstract_origin_entry, &self.dwarf, origin_unit)
                     })?;
                     let typeref = get_typeref_attribute(entry, unit)
-                        .or_else(|_| get_typeref_attribute(&abstract_origin_entry, unit))?;
+                        .or_else(|_| get_typeref_attribute(&abstract_origin_entry, origin_unit))?;
+                    let external = get_external_attribute(entry)
+                        .or_else(|| get_external_attribute(&abstract_origin_entry))
+                        .unwrap_or(false);
 
-                    Ok(Some((name, typeref, address)))
+                    Ok(Some((name, typeref, address, external)))
                 } else {
                     // usual case: there is no specification or abstract origin and all info is part of this entry
                     let name = get_name_attribute(entry, &self.dwarf, unit)?;
                     let typeref = get_typeref_attribute(entry, unit)?;
+                    let external = get_external_attribute(entry).unwrap_or(false);
 
-                    Ok(Some((name, typeref, address)))
+                    Ok(Some((name, typeref, address, external)))
                 }
             }
             None => {
@@ -332,14 +403,15 @@ fn demangle_cpp_varnames(input: &[&String]) -> HashMap<String, String> {
     for varname in input {
         // some really simple strings can be processed by the demangler, e.g "c" -> "const", which is wrong here.
         // by only processing symbols that start with _Z (variables in classes/namespaces) this problem is avoided
-        if varname.starts_with("_Z") {
-            if let Ok(sym) = cpp_demangle::Symbol::new(*varname) {
-                // exclude useless demangled names like "typeinfo for std::type_info" or "{vtable(std::type_info)}"
-                if let Ok(demangled) = sym.demangle_with_options(&demangle_opts) {
-                    if !demangled.contains(' ') && !demangled.starts_with("{vtable") {
-                        demangled_symbols.insert(demangled, (*varname).clone());
-                    }
-                }
+        if varname.starts_with("_Z")
+            && let Ok(sym) = cpp_demangle::Symbol::new(*varname)
+        {
+            // exclude useless demangled names like "typeinfo for std::type_info" or "{vtable(std::type_info)}"
+            if let Ok(demangled) = sym.demangle_with_options(&demangle_opts)
+                && !demangled.contains(' ')
+                && !demangled.starts_with("{vtable")
+            {
+                demangled_symbols.insert(demangled, (*varname).clone());
             }
         }
     }
@@ -16,6 +16,7 @@ struct TypeReaderData {
     types: HashMap<usize, TypeInfo>,
     typenames: HashMap<String, Vec<usize>>,
     wip_items: Vec<WipItemInfo>,
+    load_errors: Vec<String>,
 }
 
 impl DebugDataReader<'_> {
@@ -28,32 +29,60 @@ impl DebugDataReader<'_> {
             types: HashMap::<usize, TypeInfo>::new(),
             typenames: HashMap::<String, Vec<usize>>::new(),
             wip_items: Vec::new(),
+            load_errors: Vec::new(),
         };
         // for each variable
         for (name, var_list) in variables {
             for VarInfo { typeref, .. } in var_list {
                 // check if the type was already loaded
-                if !typereader_data.types.contains_key(typeref) {
-                    if let Some(unit_idx) = self.units.get_unit(*typeref) {
-                        // create an entries_tree iterator that makes it possible to read the DIEs of this type
-                        let dbginfo_offset = gimli::DebugInfoOffset(*typeref);
-
-                        // load one type and add it to the collection (always succeeds for correctly structured DWARF debug info)
-                        let result = self.get_type(unit_idx, dbginfo_offset, &mut typereader_data);
-                        if let Err(errmsg) = result {
-                            if self.verbose {
-                                println!("Error loading type info for variable {name}: {errmsg}");
-                            }
-                        }
-                        typereader_data.wip_items.clear();
+                if !typereader_data.types.contains_key(typeref)
+                    && let Some(unit_idx) = self.units.get_unit(*typeref)
+                {
+                    // create an entries_tree iterator that makes it possible to read the DIEs of this type
+                    let dbginfo_offset = gimli::DebugInfoOffset(*typeref);
+
+                    // load one type and add it to the collection (always succeeds for correctly structured DWARF debug info)
+                    let result = self.get_type(unit_idx, dbginfo_offset, &mut typereader_data);
+                    if let Err(errmsg) = result {
+                        typereader_data.load_errors.push(format!(
+                            "Error loading type info for variable {name}: {errmsg}"
+                        ));
                     }
+                    typereader_data.wip_items.clear();
                 }
             }
         }
 
+        self.load_errors.extend(typereader_data.load_errors);
         (typereader_data.types, typereader_data.typenames)
     }
 
+    // load the types at the given offsets, e.g. to compare types from different units
+    pub(crate) fn load_types_at(&mut self, type_offsets: &[usize]) -> HashMap<usize, TypeInfo> {
+        let mut typereader_data = TypeReaderData {
+            types: HashMap::<usize, TypeInfo>::new(),
+            typenames: HashMap::<String, Vec<usize>>::new(),
+            wip_items: Vec::new(),
+            load_errors: Vec::new(),
+        };
+        let mut requested_types = HashMap::<usize, TypeInfo>::new();
+        for typeref in type_offsets {
+            if let Some(unit_idx) = self.units.get_unit(*typeref) {
+                let dbginfo_offset = gimli::DebugInfoOffset(*typeref);
+                // declarations are not stored in typereader_data.types, so the result is kept separately
+                if let Ok(typeinfo) = self.get_type(unit_idx, dbginfo_offset, &mut typereader_data)
+                {
+                    requested_types.insert(*typeref, typeinfo);
+                }
+                typereader_data.wip_items.clear();
+            }
+        }
+
+        self.load_errors.extend(typereader_data.load_errors);
+        typereader_data.types.extend(requested_types);
+        typereader_data.types
+    }
+
     fn get_type(
         &self,
         current_unit: usize,
@@ -64,15 +93,16 @@ impl DebugDataReader<'_> {
         match self.get_type_wrapped(current_unit, dbginfo_offset, typereader_data) {
             Ok(typeinfo) => Ok(typeinfo),
             Err(errmsg) => {
-                // try to print a readable error message
-                println!("Failed to read type: {errmsg}");
-                for (idx, wip) in typereader_data.wip_items.iter().enumerate() {
-                    print!("  {:indent$}{}", "", wip.tag, indent = idx * 2);
+                // a readable error message, with the chain of types that led to the failed type
+                let mut message = format!("Failed to read type: {errmsg}");
+                for wip in &typereader_data.wip_items {
+                    message.push_str(&format!(" / {}", wip.tag));
                     if let Some(name) = &wip.name {
-                        print!(" {name}");
+                        message.push_str(&format!(" {name}"));
                     }
-                    println!(" @0x{:X}", wip.offset);
+                    message.push_str(&format!(" @0x{:X}", wip.offset));
                 }
+                typereader_data.load_errors.push(message);
 
                 // create a dummy typeinfo using DwarfDataType::Other, rather than propagate the error
                 // this allows the caller to continue, which is more useful
@@ -358,10 +388,12 @@ impl DebugDataReader<'_> {
         }
 
         // try to fix the dimension of the array, if the DW_TAG_subrange_type didn't contain enough info
-        if dim.len() == 1 && dim[0] == 0 && stride != 0 {
-            if let Some(count) = maybe_size.map(|s: u64| s / stride) {
-                dim[0] = count;
-            }
+        if dim.len() == 1
+            && dim[0] == 0
+            && stride != 0
+            && let Some(count) = maybe_size.map(|s: u64| s / stride)
+        {
+            dim[0] = count;
         }
         let size = maybe_size.unwrap_or_else(|| dim.iter().fold(stride, |acc, num| acc * num));
         Ok((
@@ -500,13 +532,18 @@ impl DebugDataReader<'_> {
                 let opt_name = get_name_attribute(child_entry, &self.dwarf, unit)
                     .map_err(|_| "missing struct/union member name".to_string());
 
-                let mut offset = get_data_member_location_attribute(
+                let mut offset = match get_data_member_location_attribute(
                     self,
                     child_entry,
                     unit.encoding(),
                     current_unit,
-                )
-                .unwrap_or(0);
+                ) {
+                    Ok(offset) => offset.unwrap_or(0),
+                    Err(errmsg) => {
+                        typereader_data.load_errors.push(errmsg);
+                        0
+                    }
+                };
                 let (new_cur_unit, new_dbginfo_offset) =
                     get_type_attribute(child_entry, &self.units, current_unit)?;
                 if let Ok(mut membertype) =
@@ -626,7 +663,7 @@ impl DebugDataReader<'_> {
                     child_entry,
                     unit.encoding(),
                     current_unit,
-                )
+                )?
                 .ok_or_else(|| "missing byte offset for inherited class".to_string())?;
                 let (new_cur_unit, new_dbginfo_offset) =
                     get_type_attribute(child_entry, &self.units, current_unit)?;
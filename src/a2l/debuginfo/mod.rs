@@ -1,6 +1,6 @@
 use indexmap::IndexMap;
 use std::collections::HashMap;
-use std::ffi::OsStr;
+use std::ffi::{OsStr, OsString};
 use std::fmt::Display;
 
 mod dwarf;
@@ -12,6 +12,9 @@ pub(crate) struct VarInfo {
     pub(crate) unit_idx: usize,
     pub(crate) function: Option<String>,
     pub(crate) namespaces: Vec<String>,
+    pub(crate) dbginfo_offset: usize,
+    /// the variable has the DW_AT_external attribute, i.e. it is visible outside of its compile unit
+    pub(crate) external: bool,
 }
 
 #[derive(Debug, Clone)]
@@ -77,19 +80,61 @@ pub(crate) struct DebugData {
     pub(crate) demangled_names: HashMap<String, String>,
     pub(crate) unit_names: Vec<Option<String>>,
     pub(crate) _sections: HashMap<String, (u64, u64)>,
+    /// variables and types that could not be read
+    pub(crate) load_errors: Vec<String>,
 }
 
 impl DebugData {
-    // load the debug info from an elf file
-    pub(crate) fn load_dwarf(filename: &OsStr, verbose: bool) -> Result<Self, String> {
-        dwarf::load_dwarf(filename, verbose)
+    // load the debug info from an elf file, and from the .dwo / .dwp files that contain its split debug info
+    pub(crate) fn load_dwarf(filename: &OsStr, split_dwarf: &[OsString]) -> Result<Self, String> {
+        dwarf::load_dwarf(filename, split_dwarf)
+    }
+
+    // load the debug info from DWARF data that is already in memory
+    pub(crate) fn load_dwarf_from(
+        dwarf: gimli::Dwarf<gimli::EndianSlice<gimli::RunTimeEndian>>,
+        endian: object::Endianness,
+    ) -> Self {
+        dwarf::load_dwarf_from(dwarf, endian)
     }
 }
 
-/// convert a full unit name, which might include a path, into a simple unit name
+/// load the types at the given debug info offsets, without loading any variables.
+/// Also returns the errors for the types that could not be read
+pub(crate) fn load_types(
+    dwarf: gimli::Dwarf<gimli::EndianSlice<gimli::RunTimeEndian>>,
+    endian: object::Endianness,
+    type_offsets: &[usize],
+) -> (HashMap<usize, TypeInfo>, Vec<String>) {
+    dwarf::load_types_from(dwarf, endian, type_offsets)
+}
+
+/// the type tables and the name mapping that are needed to compare types from two different
+/// debug data sets, e.g. the types of a variable before and after obfuscation
+pub(crate) struct TypeComparison<'a> {
+    pub(crate) types: &'a HashMap<usize, TypeInfo>,
+    pub(crate) other_types: &'a HashMap<usize, TypeInfo>,
+    pub(crate) name_map: &'a HashMap<String, String>,
+}
+
+impl TypeComparison<'_> {
+    // names that are not in the mapping were not obfuscated
+    fn map_name(&self, name: &str) -> String {
+        self.name_map
+            .get(name)
+            .cloned()
+            .unwrap_or_else(|| name.to_string())
+    }
+}
+
+/// get the simple name of a unit, as it is used in the {CompileUnit:...} qualifier of a symbol link
 pub(crate) fn make_simple_unit_name(debug_data: &DebugData, unit_idx: usize) -> Option<String> {
     let full_name = debug_data.unit_names.get(unit_idx)?.as_deref()?;
+    Some(simple_unit_name(full_name))
+}
 
+/// convert a full unit name, which might include a path, into a simple unit name
+pub(crate) fn simple_unit_name(full_name: &str) -> String {
     let file_name = if let Some(pos) = full_name.rfind('\\') {
         &full_name[(pos + 1)..]
     } else if let Some(pos) = full_name.rfind('/') {
@@ -98,7 +143,7 @@ pub(crate) fn make_simple_unit_name(debug_data: &DebugData, unit_idx: usize) ->
         full_name
     };
 
-    Some(file_name.replace('.', "_"))
+    file_name.replace('.', "_")
 }
 
 impl TypeInfo {
@@ -115,7 +160,7 @@ impl TypeInfo {
             DbgDataType::Float => 4,
             DbgDataType::Double => 8,
             DbgDataType::Bitfield { basetype, .. } => basetype.get_size(),
-            DbgDataType::Pointer(size/* , _ */)
+            DbgDataType::Pointer(size /* , _ */)
             | DbgDataType::Other(size)
             | DbgDataType::Struct { size, .. }
             | DbgDataType::Class { size, .. }
@@ -165,175 +210,148 @@ impl TypeInfo {
         }
     }
 
-    // // not using PartialEq, because not all fields are considered for this comparison
-    // pub(crate) fn compare(&self, other: &TypeInfo, types: &HashMap<usize, TypeInfo>) -> bool {
-    //     self.compare_internal(other, types, 0)
-    // }
+    /// compare the shape of two types, which may come from different debug data: the kinds of the types,
+    /// their sizes, the offsets and shapes of the members, and the enumerator values.
+    /// The names of members and enumerators in self are translated with name_map before they are compared
+    pub(crate) fn compare(&self, other: &TypeInfo, context: &TypeComparison) -> bool {
+        self.compare_internal(other, context, 0)
+    }
 
-    // fn compare_internal(
-    //     &self,
-    //     other: &TypeInfo,
-    //     types: &HashMap<usize, TypeInfo>,
-    //     depth: usize,
-    // ) -> bool {
-    //     let type_1 = self.get_reference(types);
-    //     let type_2 = other.get_reference(types);
+    fn compare_internal(&self, other: &TypeInfo, context: &TypeComparison, depth: usize) -> bool {
+        let type_1 = self.get_reference(context.types);
+        let type_2 = other.get_reference(context.other_types);
+        if depth > Self::MAX_RECURSION_DEPTH {
+            // only the outline of deeply nested types is compared
+            return std::mem::discriminant(&type_1.datatype)
+                == std::mem::discriminant(&type_2.datatype)
+                && type_1.get_size() == type_2.get_size();
+        }
 
-    //     type_1._dbginfo_offset == type_2._dbginfo_offset
-    //         || (type_1.name == type_2.name
-    //             && match (&type_1.datatype, &type_2.datatype) {
-    //                 (DbgDataType::Uint8, DbgDataType::Uint8)
-    //                 | (DbgDataType::Uint16, DbgDataType::Uint16)
-    //                 | (DbgDataType::Uint32, DbgDataType::Uint32)
-    //                 | (DbgDataType::Uint64, DbgDataType::Uint64)
-    //                 | (DbgDataType::Sint8, DbgDataType::Sint8)
-    //                 | (DbgDataType::Sint16, DbgDataType::Sint16)
-    //                 | (DbgDataType::Sint32, DbgDataType::Sint32)
-    //                 | (DbgDataType::Sint64, DbgDataType::Sint64)
-    //                 | (DbgDataType::Float, DbgDataType::Float)
-    //                 | (DbgDataType::Double, DbgDataType::Double) => true,
-    //                 (
-    //                     DbgDataType::Enum {
-    //                         size,
-    //                         _signed: signed,
-    //                         enumerators,
-    //                     },
-    //                     DbgDataType::Enum {
-    //                         size: size2,
-    //                         _signed: signed2,
-    //                         enumerators: enumerators2,
-    //                     },
-    //                 ) => size == size2 && signed == signed2 && enumerators == enumerators2,
-    //                 (
-    //                     DbgDataType::Array {
-    //                         size,
-    //                         dim,
-    //                         stride,
-    //                         arraytype,
-    //                     },
-    //                     DbgDataType::Array {
-    //                         size: size2,
-    //                         dim: dim2,
-    //                         stride: stride2,
-    //                         arraytype: arraytype2,
-    //                     },
-    //                 ) => {
-    //                     size == size2
-    //                         && dim == dim2
-    //                         && stride == stride2
-    //                         && arraytype.compare_internal(arraytype2, types, depth + 1)
-    //                 }
-    //                 (
-    //                     DbgDataType::Pointer(size1, dest_offset1),
-    //                     DbgDataType::Pointer(size2, dest_offset2),
-    //                 ) => {
-    //                     size1 == size2
-    //                         && if dest_offset1 == dest_offset2 {
-    //                             true
-    //                         } else if let (Some(dest_type1), Some(dest_type2)) =
-    //                             (types.get(dest_offset1), types.get(dest_offset2))
-    //                         {
-    //                             // can't always call ref1.compare(&ref2) here, because this could result in infinite recursion
-    //                             if depth < Self::MAX_RECURSION_DEPTH {
-    //                                 dest_type1.compare_internal(dest_type2, types, depth + 1)
-    //                             } else {
-    //                                 // when we're not using compare(), we need to follow TypeRef (if any) to the referenced type
-    //                                 let dest1_deref = dest_type1.get_reference(types);
-    //                                 let dest2_deref = dest_type2.get_reference(types);
-    //                                 dest1_deref.name == dest2_deref.name
-    //                                     && std::mem::discriminant(&dest1_deref.datatype)
-    //                                         == std::mem::discriminant(&dest2_deref.datatype)
-    //                                     && dest1_deref.get_size() == dest2_deref.get_size()
-    //                             }
-    //                         } else {
-    //                             false
-    //                         }
-    //                 }
-    //                 (DbgDataType::Other(size1), DbgDataType::Other(size2)) => size1 == size2,
-    //                 (
-    //                     DbgDataType::Bitfield {
-    //                         basetype,
-    //                         _bit_offset: bit_offset,
-    //                         _bit_size: bit_size,
-    //                     },
-    //                     DbgDataType::Bitfield {
-    //                         basetype: basetype2,
-    //                         _bit_offset: bit_offset2,
-    //                         _bit_size: bit_size2,
-    //                     },
-    //                 ) => {
-    //                     bit_offset == bit_offset2
-    //                         && bit_size == bit_size2
-    //                         && basetype.compare_internal(basetype2, types, depth + 1)
-    //                 }
-    //                 (
-    //                     DbgDataType::Struct { size, members },
-    //                     DbgDataType::Struct {
-    //                         size: size2,
-    //                         members: members2,
-    //                     },
-    //                 ) => size == size2 && Self::compare_members(members, members2, types, depth),
-    //                 (
-    //                     DbgDataType::Union { size, members },
-    //                     DbgDataType::Union {
-    //                         size: size2,
-    //                         members: members2,
-    //                     },
-    //                 ) => size == size2 && Self::compare_members(members, members2, types, depth),
-    //                 (
-    //                     DbgDataType::Class {
-    //                         size,
-    //                         members,
-    //                         inheritance,
-    //                     },
-    //                     DbgDataType::Class {
-    //                         size: size2,
-    //                         members: members2,
-    //                         inheritance: inheritance2,
-    //                     },
-    //                 ) => {
-    //                     size == size2
-    //                         && Self::compare_members(members, members2, types, depth)
-    //                         && Self::compare_members(inheritance, inheritance2, types, depth)
-    //                 }
-    //                 (DbgDataType::FuncPtr(size1), DbgDataType::FuncPtr(size2)) => size1 == size2,
-    //                 _ => false,
-    //             })
-    // }
+        match (&type_1.datatype, &type_2.datatype) {
+            (DbgDataType::Uint8, DbgDataType::Uint8)
+            | (DbgDataType::Uint16, DbgDataType::Uint16)
+            | (DbgDataType::Uint32, DbgDataType::Uint32)
+            | (DbgDataType::Uint64, DbgDataType::Uint64)
+            | (DbgDataType::Sint8, DbgDataType::Sint8)
+            | (DbgDataType::Sint16, DbgDataType::Sint16)
+            | (DbgDataType::Sint32, DbgDataType::Sint32)
+            | (DbgDataType::Sint64, DbgDataType::Sint64)
+            | (DbgDataType::Float, DbgDataType::Float)
+            | (DbgDataType::Double, DbgDataType::Double) => true,
+            (
+                DbgDataType::Enum {
+                    size,
+                    _signed: signed,
+                    enumerators,
+                },
+                DbgDataType::Enum {
+                    size: size2,
+                    _signed: signed2,
+                    enumerators: enumerators2,
+                },
+            ) => {
+                // the enumerators may have been reordered
+                let mut mapped: Vec<_> = enumerators
+                    .iter()
+                    .map(|(name, value)| (context.map_name(name), *value))
+                    .collect();
+                let mut enumerators2 = enumerators2.clone();
+                mapped.sort();
+                enumerators2.sort();
+                size == size2 && signed == signed2 && mapped == enumerators2
+            }
+            (
+                DbgDataType::Array {
+                    size,
+                    dim,
+                    stride,
+                    arraytype,
+                },
+                DbgDataType::Array {
+                    size: size2,
+                    dim: dim2,
+                    stride: stride2,
+                    arraytype: arraytype2,
+                },
+            ) => {
+                size == size2
+                    && dim == dim2
+                    && stride == stride2
+                    && arraytype.compare_internal(arraytype2, context, depth + 1)
+            }
+            (DbgDataType::Pointer(size1), DbgDataType::Pointer(size2))
+            | (DbgDataType::Other(size1), DbgDataType::Other(size2))
+            | (DbgDataType::FuncPtr(size1), DbgDataType::FuncPtr(size2)) => size1 == size2,
+            (
+                DbgDataType::Bitfield {
+                    basetype,
+                    _bit_offset: bit_offset,
+                    _bit_size: bit_size,
+                },
+                DbgDataType::Bitfield {
+                    basetype: basetype2,
+                    _bit_offset: bit_offset2,
+                    _bit_size: bit_size2,
+                },
+            ) => {
+                bit_offset == bit_offset2
+                    && bit_size == bit_size2
+                    && basetype.compare_internal(basetype2, context, depth + 1)
+            }
+            (
+                DbgDataType::Struct { size, members },
+                DbgDataType::Struct {
+                    size: size2,
+                    members: members2,
+                },
+            )
+            | (
+                DbgDataType::Union { size, members },
+                DbgDataType::Union {
+                    size: size2,
+                    members: members2,
+                },
+            ) => size == size2 && Self::compare_members(members, members2, context, depth),
+            (
+                DbgDataType::Class {
+                    size,
+                    members,
+                    inheritance,
+                },
+                DbgDataType::Class {
+                    size: size2,
+                    members: members2,
+                    inheritance: inheritance2,
+                },
+            ) => {
+                size == size2
+                    && Self::compare_members(members, members2, context, depth)
+                    && Self::compare_members(inheritance, inheritance2, context, depth)
+            }
+            _ => false,
+        }
+    }
 
-    // fn compare_members(
-    //     members1: &IndexMap<String, (TypeInfo, u64)>,
-    //     members2: &IndexMap<String, (TypeInfo, u64)>,
-    //     types: &HashMap<usize, TypeInfo>,
-    //     depth: usize,
-    // ) -> bool {
-    //     if members1.len() != members2.len() {
-    //         return false;
-    //     }
-    //     for (member1_name, (member1_type, member1_offset)) in members1 {
-    //         let Some((member2_type, member2_offset)) = members2.get(member1_name) else {
-    //             return false;
-    //         };
-    //         if member1_offset != member2_offset {
-    //             return false;
-    //         }
-    //         if depth < Self::MAX_RECURSION_DEPTH {
-    //             if !member1_type.compare_internal(member2_type, types, depth + 1) {
-    //                 return false;
-    //             }
-    //         } else {
-    //             let member1_deref = member1_type.get_reference(types);
-    //             let member2_deref = member2_type.get_reference(types);
-    //             if std::mem::discriminant(&member1_deref.datatype)
-    //                 != std::mem::discriminant(&member2_deref.datatype)
-    //                 || member1_deref.name != member2_deref.name
-    //             {
-    //                 return false;
-    //             }
-    //         }
-    //     }
-    //     true
-    // }
+    fn compare_members(
+        members1: &IndexMap<String, (TypeInfo, u64)>,
+        members2: &IndexMap<String, (TypeInfo, u64)>,
+        context: &TypeComparison,
+        depth: usize,
+    ) -> bool {
+        members1.len() == members2.len()
+            && members1
+                .iter()
+                .all(|(member1_name, (member1_type, member1_offset))| {
+                    members2
+                        .get(context.map_name(member1_name).as_str())
+                        .is_some_and(|(member2_type, member2_offset)| {
+                            member1_offset == member2_offset
+                                && member1_type.compare_internal(member2_type, context, depth + 1)
+                        })
+                })
+    }
+
+    const MAX_RECURSION_DEPTH: usize = 20;
 }
 
 impl Display for TypeInfo {
@@ -0,0 +1,205 @@
+use super::debuginfo::DebugData;
+use super::{ifdata, symbol::find_symbol};
+use crate::report::Report;
+use a2lfile::A2lObjectName;
+use rand::{RngExt, rngs::StdRng};
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+
+/// the EPK (software identifier) of an a2l file, and its address in the elf file
+#[derive(Debug)]
+pub(crate) struct EpkData {
+    pub(crate) identifier: String,
+    pub(crate) address: Option<u64>,
+}
+
+/// read the EPK from the MOD_PAR of the first module that has one. The address is taken from
+/// ADDR_EPK, or from the symbol in the CANAPE_ADDRESS_UPDATE EPK_ADDRESS entry
+pub(crate) fn read_epk(
+    filename_in: &OsStr,
+    filename_elf: &OsStr,
+    split_dwarf: &[OsString],
+    report: &mut Report,
+) -> Result<Option<EpkData>, String> {
+    let (a2l, _) = a2lfile::load(
+        filename_in,
+        Some(ifdata::A2MLVECTOR_TEXT.to_string()),
+        false,
+    )
+    .map_err(|e| e.to_string())?;
+
+    for module in &a2l.project.module {
+        let Some(mod_par) = &module.mod_par else {
+            continue;
+        };
+        let Some(epk) = &mod_par.epk else {
+            continue;
+        };
+        let mut address = mod_par
+            .addr_epk
+            .first()
+            .map(|addr_epk| u64::from(addr_epk.address));
+        if address.is_none()
+            && let Some((epk_sym, offset)) = epk_symbols(&module.if_data).into_iter().next()
+        {
+            let debuginfo =
+                DebugData::load_dwarf(filename_elf, split_dwarf).map_err(|e| e.to_string())?;
+            report.add_debug_info_errors(&debuginfo.load_errors);
+            // the input elf file has not been obfuscated, so the symbol names don't need to be mapped
+            address = find_symbol(&epk_sym, &debuginfo, &HashMap::new())
+                .ok()
+                .map(|sym_info| sym_info.address.wrapping_add_signed(offset.into()));
+        }
+        return Ok(Some(EpkData {
+            identifier: epk.identifier.clone(),
+            address,
+        }));
+    }
+    Ok(None)
+}
+
+/// generate a new identifier with the same format as the original: letters are replaced by
+/// random letters and digits by random digits. Everything else, e.g. '_' or '.', is kept
+pub(crate) fn generate_identifier(identifier: &str, rng: &mut StdRng) -> String {
+    identifier
+        .chars()
+        .map(|c| {
+            if c.is_ascii_uppercase() {
+                char::from(rng.random_range(b'A'..=b'Z'))
+            } else if c.is_ascii_lowercase() {
+                char::from(rng.random_range(b'a'..=b'z'))
+            } else if c.is_ascii_digit() {
+                char::from(rng.random_range(b'0'..=b'9'))
+            } else {
+                c
+            }
+        })
+        .collect()
+}
+
+/// replace the EPK and ADDR_EPK in the MOD_PAR. If the EPK has no address in the output elf file,
+/// or the address does not fit into 32 bits, ADDR_EPK is removed, because the original address
+/// would reveal the memory layout
+pub(crate) fn replace_epk(module: &mut a2lfile::Module, new_epk: &EpkData, report: &mut Report) {
+    let Some(mod_par) = &mut module.mod_par else {
+        return;
+    };
+    if let Some(epk) = &mut mod_par.epk {
+        epk.identifier = new_epk.identifier.clone();
+    }
+    // ADDR_EPK only holds 32-bit addresses
+    let removal_reason = match new_epk.address.map(u32::try_from) {
+        Some(Ok(address)) => {
+            for addr_epk in &mut mod_par.addr_epk {
+                addr_epk.address = address;
+            }
+            None
+        }
+        Some(Err(_)) => {
+            Some("the address of the EPK in the output elf file does not fit into 32 bits")
+        }
+        None => Some("the EPK has no address in the output elf file"),
+    };
+    if let Some(reason) = removal_reason
+        && !mod_par.addr_epk.is_empty()
+    {
+        mod_par.addr_epk.clear();
+        report.add_warning(format!(
+            "{reason}, ADDR_EPK was removed from module {}",
+            module.get_name()
+        ));
+    }
+}
+
+/// map the symbols in the CANAPE_ADDRESS_UPDATE EPK_ADDRESS entries of the module to the obfuscated names
+pub(crate) fn obfuscate_epk_symbols(
+    if_data: &mut [a2lfile::IfData],
+    debuginfo: &DebugData,
+    dwarf_stringmapping: &HashMap<String, String>,
+    report: &mut Report,
+) {
+    for ifdata in if_data {
+        if let Some(mut decoded_ifdata) = ifdata::A2mlVector::load_from_ifdata(ifdata)
+            && let Some(address_update) = &mut decoded_ifdata.canape_address_update
+            && !address_update.epk_address.is_empty()
+        {
+            for epk_address in &mut address_update.epk_address {
+                epk_address.epk_sym =
+                    match find_symbol(&epk_address.epk_sym, debuginfo, dwarf_stringmapping) {
+                        Ok(sym_info) => sym_info.name,
+                        Err(error) => {
+                            report.add_unresolved_symbol(&epk_address.epk_sym, error);
+                            super::obfuscate_string(&epk_address.epk_sym)
+                        }
+                    };
+            }
+            decoded_ifdata.store_to_ifdata(ifdata);
+        }
+    }
+}
+
+// get the symbols and offsets of all EPK_ADDRESS entries in the IF_DATA of a module
+fn epk_symbols(if_data: &[a2lfile::IfData]) -> Vec<(String, i32)> {
+    if_data
+        .iter()
+        .filter_map(ifdata::A2mlVector::load_from_ifdata)
+        .filter_map(|decoded_ifdata| decoded_ifdata.canape_address_update)
+        .flat_map(|address_update| address_update.epk_address)
+        .map(|epk_address| (epk_address.epk_sym, epk_address.offset))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn module_with_epk() -> a2lfile::Module {
+        let text = r#"
+ASAP2_VERSION 1 71
+/begin PROJECT Project ""
+  /begin MODULE Module ""
+    /begin MOD_PAR ""
+      EPK "EPK_1234"
+      ADDR_EPK 0x1000
+    /end MOD_PAR
+  /end MODULE
+/end PROJECT
+"#;
+        let (mut a2l, _) = a2lfile::load_from_string(text, None, false).unwrap();
+        a2l.project.module.pop().unwrap()
+    }
+
+    fn replaced_addr_epk(address: Option<u64>) -> (Vec<u32>, Report) {
+        let mut module = module_with_epk();
+        let new_epk = EpkData {
+            identifier: "EPK_5678".to_string(),
+            address,
+        };
+        let mut report = Report::default();
+        replace_epk(&mut module, &new_epk, &mut report);
+        let mod_par = module.mod_par.unwrap();
+        assert_eq!(mod_par.epk.unwrap().identifier, "EPK_5678");
+        let addresses = mod_par.addr_epk.iter().map(|addr| addr.address).collect();
+        (addresses, report)
+    }
+
+    #[test]
+    fn replace_epk_address() {
+        let (addresses, report) = replaced_addr_epk(Some(0x8000_2000));
+        assert_eq!(addresses, [0x8000_2000]);
+        assert!(report.warnings.is_empty());
+    }
+
+    #[test]
+    fn remove_epk_address() {
+        // the EPK was not found in the elf file
+        let (addresses, report) = replaced_addr_epk(None);
+        assert!(addresses.is_empty());
+        assert_eq!(report.warnings.len(), 1);
+
+        // the randomized address of a 64-bit elf file can be above 4 GiB
+        let (addresses, report) = replaced_addr_epk(Some(0x1_0000_2000));
+        assert!(addresses.is_empty());
+        assert_eq!(report.warnings.len(), 1);
+    }
+}
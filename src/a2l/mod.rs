@@ -1,34 +1,59 @@
-use a2lfile::A2lObjectName;
-use std::{collections::HashMap, ffi::OsStr};
-use symbol::find_symbol;
+use crate::keeplist::KeepList;
+use crate::options::{Options, OutputOrder};
+use crate::report::Report;
+use a2lfile::{A2lObject, A2lObjectName, ItemList};
+use rand::{rngs::StdRng, seq::SliceRandom};
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::{OsStr, OsString},
+};
+use symbol::{SymbolInfo, find_symbol};
 
-mod debuginfo;
+mod calibration;
+pub(crate) mod debuginfo;
+mod epk;
 mod ifdata;
 mod symbol;
+mod verify;
 
+pub(crate) use calibration::{CalibrationObject, ValueType, calibration_objects};
+pub(crate) use epk::{EpkData, generate_identifier, read_epk};
+pub(crate) use verify::verify_output;
+
+/// obfuscate the input a2l file of the options so that it matches the obfuscated output elf file
 pub(crate) fn obfuscate_a2l(
-    filename_in: &OsStr,
-    filename_out: &OsStr,
-    filename_elf: &OsStr,
+    options: &Options,
     dwarf_stringmapping: &HashMap<String, String>,
+    new_epk: Option<&EpkData>,
+    rng: &mut StdRng,
+    report: &mut Report,
 ) -> Result<(), String> {
+    let keep_list = &options.keep_list;
     let (mut a2l, _) = a2lfile::load(
-        filename_in,
+        &options.a2l_in,
         Some(ifdata::A2MLVECTOR_TEXT.to_string()),
         false,
     )
     .map_err(|e| e.to_string())?;
 
+    // the obfuscated elf file never contains split debug info
     let debuginfo =
-        debuginfo::DebugData::load_dwarf(filename_elf, false).map_err(|e| e.to_string())?;
+        debuginfo::DebugData::load_dwarf(&options.elf_out, &[]).map_err(|e| e.to_string())?;
+    report.add_debug_info_errors(&debuginfo.load_errors);
 
-    a2l.project.name = obfuscate_string(&a2l.project.name);
+    let project_name = obfuscate_object_name(&a2l.project.name, keep_list);
+    if project_name != a2l.project.name {
+        report.add_renamed_object("PROJECT");
+    }
+    a2l.project.name = project_name;
     a2l.project.long_identifier = obfuscate_string(&a2l.project.long_identifier);
 
     for idx in 0..a2l.project.module.len() {
-        a2l.project
-            .module
-            .rename_item(idx, &obfuscate_string(a2l.project.module[idx].get_name()));
+        let module_name = obfuscate_object_name(a2l.project.module[idx].get_name(), keep_list);
+        if module_name != a2l.project.module[idx].get_name() {
+            report.add_renamed_object("MODULE");
+        }
+        a2l.project.module.rename_item(idx, &module_name);
         a2l.project.module[idx].long_identifier =
             obfuscate_string(&a2l.project.module[idx].long_identifier);
 
@@ -36,43 +61,205 @@ pub(crate) fn obfuscate_a2l(
             &mut a2l.project.module[idx],
             &debuginfo,
             dwarf_stringmapping,
+            keep_list,
+            report,
         );
+        epk::obfuscate_epk_symbols(
+            &mut a2l.project.module[idx].if_data,
+            &debuginfo,
+            dwarf_stringmapping,
+            report,
+        );
+        if let Some(new_epk) = new_epk {
+            epk::replace_epk(&mut a2l.project.module[idx], new_epk, report);
+        }
     }
 
-    a2l.write(filename_out, None).map_err(|e| e.to_string())?;
+    match options.order {
+        OutputOrder::Original => {}
+        OutputOrder::Shuffle => {
+            // sorting first places all objects of each type in a contiguous block, which is then shuffled
+            a2l.sort();
+            shuffle_objectlist(&mut a2l.project.module, rng);
+            for module in &mut a2l.project.module {
+                shuffle_module(module, rng);
+            }
+        }
+        OutputOrder::Sort => a2l.sort(),
+    }
+
+    a2l.write(&options.a2l_out, None)
+        .map_err(|e| e.to_string())?;
     Ok(())
 }
 
+/// find the debug info offsets of all variables that are referenced by a SYMBOL_LINK or an IF_DATA LINK_MAP
+pub(crate) fn referenced_variables(
+    filename_in: &OsStr,
+    filename_elf: &OsStr,
+    split_dwarf: &[OsString],
+    report: &mut Report,
+) -> Result<HashSet<usize>, String> {
+    let (a2l, _) = a2lfile::load(
+        filename_in,
+        Some(ifdata::A2MLVECTOR_TEXT.to_string()),
+        false,
+    )
+    .map_err(|e| e.to_string())?;
+
+    let debuginfo =
+        debuginfo::DebugData::load_dwarf(filename_elf, split_dwarf).map_err(|e| e.to_string())?;
+    report.add_debug_info_errors(&debuginfo.load_errors);
+
+    // the input elf file has not been obfuscated, so the symbol names don't need to be mapped
+    let no_mapping = HashMap::new();
+    let mut variables = HashSet::new();
+
+    for module in &a2l.project.module {
+        for (_, symbol_name) in linked_symbols(module) {
+            if let Ok(sym_info) = find_symbol(&symbol_name, &debuginfo, &no_mapping) {
+                variables.insert(sym_info.var_dbginfo_offset);
+            }
+        }
+    }
+
+    Ok(variables)
+}
+
+// get the names of all objects in the module that have a SYMBOL_LINK or an IF_DATA LINK_MAP,
+// together with the linked symbol names
+fn linked_symbols(module: &a2lfile::Module) -> Vec<(&str, String)> {
+    let items = module
+        .characteristic
+        .iter()
+        .map(|item| (item.get_name(), &item.symbol_link, &item.if_data))
+        .chain(
+            module
+                .measurement
+                .iter()
+                .map(|item| (item.get_name(), &item.symbol_link, &item.if_data)),
+        )
+        .chain(
+            module
+                .axis_pts
+                .iter()
+                .map(|item| (item.get_name(), &item.symbol_link, &item.if_data)),
+        )
+        .chain(
+            module
+                .blob
+                .iter()
+                .map(|item| (item.get_name(), &item.symbol_link, &item.if_data)),
+        )
+        .chain(
+            module
+                .instance
+                .iter()
+                .map(|item| (item.get_name(), &item.symbol_link, &item.if_data)),
+        );
+
+    let mut symbols = vec![];
+    for (name, symbol_link, if_data) in items {
+        if let Some(symbol_link) = symbol_link {
+            symbols.push((name, symbol_link.symbol_name.clone()));
+        }
+        for ifdata in if_data {
+            if let Some(decoded_ifdata) = ifdata::A2mlVector::load_from_ifdata(ifdata)
+                && let Some(canape_ext) = decoded_ifdata.canape_ext
+                && let Some(link_map) = canape_ext.link_map
+            {
+                symbols.push((name, link_map.symbol_name));
+            }
+        }
+    }
+    symbols
+}
+
+/// get the names of the objects in the a2l file that are renamed by obfuscate_a2l,
+/// i.e. all names of the renamed object types that are not on the keep list
+pub(crate) fn renamed_object_names(
+    filename_in: &OsStr,
+    keep_list: &KeepList,
+) -> Result<Vec<String>, String> {
+    let (a2l, _) = a2lfile::load(
+        filename_in,
+        Some(ifdata::A2MLVECTOR_TEXT.to_string()),
+        false,
+    )
+    .map_err(|e| e.to_string())?;
+
+    let mut names = vec![a2l.project.get_name()];
+    for module in &a2l.project.module {
+        names.push(module.get_name());
+        names.extend(module.characteristic.iter().map(|item| item.get_name()));
+        names.extend(module.measurement.iter().map(|item| item.get_name()));
+        names.extend(module.axis_pts.iter().map(|item| item.get_name()));
+        names.extend(module.record_layout.iter().map(|item| item.get_name()));
+        names.extend(module.function.iter().map(|item| item.get_name()));
+        names.extend(module.group.iter().map(|item| item.get_name()));
+        names.extend(module.compu_method.iter().map(|item| item.get_name()));
+        names.extend(module.compu_tab.iter().map(|item| item.get_name()));
+        names.extend(module.compu_vtab.iter().map(|item| item.get_name()));
+        names.extend(module.compu_vtab_range.iter().map(|item| item.get_name()));
+    }
+
+    Ok(names
+        .into_iter()
+        .filter(|name| !keep_list.contains(name))
+        .map(str::to_string)
+        .collect())
+}
+
 fn obfuscate_module(
     module: &mut a2lfile::Module,
     debuginfo: &debuginfo::DebugData,
     dwarf_stringmapping: &HashMap<String, String>,
+    keep_list: &KeepList,
+    report: &mut Report,
 ) {
+    // the enumerators of the enum types of the variables that use each COMPU_METHOD
+    let mut conversion_enums = HashMap::<String, Vec<&[(String, i64)]>>::new();
+
     // obfuscate all CHARACTERISTICs in the module
     let mut name_map = HashMap::<String, String>::new();
     for idx in 0..module.characteristic.len() {
         let old_name = module.characteristic[idx].get_name().to_string();
         module
             .characteristic
-            .rename_item(idx, &obfuscate_string(&old_name));
+            .rename_item(idx, &obfuscate_object_name(&old_name, keep_list));
         module.characteristic[idx].long_identifier =
             obfuscate_string(&module.characteristic[idx].long_identifier);
         let new_name = module.characteristic[idx].get_name().to_string();
-        name_map.insert(old_name, new_name);
+        if new_name != old_name {
+            report.add_renamed_object("CHARACTERISTIC");
+        }
+        name_map.insert(old_name, new_name.clone());
 
         if let Some(display_identifier) = &mut module.characteristic[idx].display_identifier {
             display_identifier.display_name = obfuscate_string(&display_identifier.display_name);
         }
 
-        obfuscate_symbol_link(
+        let sym_info = obfuscate_symbol_link(
             &mut module.characteristic[idx].symbol_link,
+            &new_name,
             debuginfo,
             dwarf_stringmapping,
+            report,
         );
+        if let Some(enumerators) =
+            sym_info.and_then(|sym_info| get_enumerators(&sym_info, debuginfo))
+        {
+            conversion_enums
+                .entry(module.characteristic[idx].conversion.clone())
+                .or_default()
+                .push(enumerators);
+        }
         obfuscate_ifdata(
             &mut module.characteristic[idx].if_data,
+            &new_name,
             debuginfo,
             dwarf_stringmapping,
+            report,
         );
 
         // erase the address of the characteristic
@@ -86,25 +273,40 @@ fn obfuscate_module(
         let old_name = module.measurement[idx].get_name().to_string();
         module
             .measurement
-            .rename_item(idx, &obfuscate_string(&old_name));
+            .rename_item(idx, &obfuscate_object_name(&old_name, keep_list));
         module.measurement[idx].long_identifier =
             obfuscate_string(&module.measurement[idx].long_identifier);
         let new_name = module.measurement[idx].get_name().to_string();
-        name_map.insert(old_name, new_name);
+        if new_name != old_name {
+            report.add_renamed_object("MEASUREMENT");
+        }
+        name_map.insert(old_name, new_name.clone());
 
         if let Some(display_identifier) = &mut module.measurement[idx].display_identifier {
             display_identifier.display_name = obfuscate_string(&display_identifier.display_name);
         }
 
-        obfuscate_symbol_link(
+        let sym_info = obfuscate_symbol_link(
             &mut module.measurement[idx].symbol_link,
+            &new_name,
             debuginfo,
             dwarf_stringmapping,
+            report,
         );
+        if let Some(enumerators) =
+            sym_info.and_then(|sym_info| get_enumerators(&sym_info, debuginfo))
+        {
+            conversion_enums
+                .entry(module.measurement[idx].conversion.clone())
+                .or_default()
+                .push(enumerators);
+        }
         obfuscate_ifdata(
             &mut module.measurement[idx].if_data,
+            &new_name,
             debuginfo,
             dwarf_stringmapping,
+            report,
         );
 
         // erase the address of the measurement
@@ -120,25 +322,40 @@ fn obfuscate_module(
         let old_name = module.axis_pts[idx].get_name().to_string();
         module
             .axis_pts
-            .rename_item(idx, &obfuscate_string(&old_name));
+            .rename_item(idx, &obfuscate_object_name(&old_name, keep_list));
         module.axis_pts[idx].long_identifier =
             obfuscate_string(&module.axis_pts[idx].long_identifier);
         let new_name = module.axis_pts[idx].get_name().to_string();
-        name_map.insert(old_name, new_name);
+        if new_name != old_name {
+            report.add_renamed_object("AXIS_PTS");
+        }
+        name_map.insert(old_name, new_name.clone());
 
         if let Some(display_identifier) = &mut module.axis_pts[idx].display_identifier {
             display_identifier.display_name = obfuscate_string(&display_identifier.display_name);
         }
 
-        obfuscate_symbol_link(
+        let sym_info = obfuscate_symbol_link(
             &mut module.axis_pts[idx].symbol_link,
+            &new_name,
             debuginfo,
             dwarf_stringmapping,
+            report,
         );
+        if let Some(enumerators) =
+            sym_info.and_then(|sym_info| get_enumerators(&sym_info, debuginfo))
+        {
+            conversion_enums
+                .entry(module.axis_pts[idx].conversion.clone())
+                .or_default()
+                .push(enumerators);
+        }
         obfuscate_ifdata(
             &mut module.axis_pts[idx].if_data,
+            &new_name,
             debuginfo,
             dwarf_stringmapping,
+            report,
         );
 
         // erase the address of the axis points
@@ -152,8 +369,11 @@ fn obfuscate_module(
         let old_name = module.record_layout[idx].get_name().to_string();
         module
             .record_layout
-            .rename_item(idx, &obfuscate_string(&old_name));
+            .rename_item(idx, &obfuscate_object_name(&old_name, keep_list));
         let new_name = module.record_layout[idx].get_name().to_string();
+        if new_name != old_name {
+            report.add_renamed_object("RECORD_LAYOUT");
+        }
         name_map.insert(old_name, new_name);
     }
     update_record_layout_xrefs(module, &name_map);
@@ -164,10 +384,13 @@ fn obfuscate_module(
         let old_name = module.function[idx].get_name().to_string();
         module
             .function
-            .rename_item(idx, &obfuscate_string(&old_name));
+            .rename_item(idx, &obfuscate_object_name(&old_name, keep_list));
         module.function[idx].long_identifier =
             obfuscate_string(&module.function[idx].long_identifier);
         let new_name = module.function[idx].get_name().to_string();
+        if new_name != old_name {
+            report.add_renamed_object("FUNCTION");
+        }
         name_map.insert(old_name, new_name);
     }
     update_function_xrefs(module, &name_map);
@@ -176,21 +399,39 @@ fn obfuscate_module(
     name_map.clear();
     for idx in 0..module.group.len() {
         let old_name = module.group[idx].get_name().to_string();
-        module.group.rename_item(idx, &obfuscate_string(&old_name));
+        module
+            .group
+            .rename_item(idx, &obfuscate_object_name(&old_name, keep_list));
         module.group[idx].long_identifier = obfuscate_string(&module.group[idx].long_identifier);
         let new_name = module.group[idx].get_name().to_string();
+        if new_name != old_name {
+            report.add_renamed_object("GROUP");
+        }
         name_map.insert(old_name, new_name);
     }
     update_group_xrefs(module, &name_map);
 
     // obfuscate all COMPU_METHODs in the module
     name_map.clear();
+    let mut vtab_enums = HashMap::<String, Vec<&[(String, i64)]>>::new();
     for idx in 0..module.compu_method.len() {
         let old_name = module.compu_method[idx].get_name().to_string();
+        // a verbal conversion table that is used for an enum variable often mirrors the enum
+        if let Some(compu_tab_ref) = &module.compu_method[idx].compu_tab_ref
+            && let Some(enums) = conversion_enums.remove(&old_name)
+        {
+            vtab_enums
+                .entry(compu_tab_ref.conversion_table.clone())
+                .or_default()
+                .extend(enums);
+        }
         module
             .compu_method
-            .rename_item(idx, &obfuscate_string(&old_name));
+            .rename_item(idx, &obfuscate_object_name(&old_name, keep_list));
         let new_name = module.compu_method[idx].get_name().to_string();
+        if new_name != old_name {
+            report.add_renamed_object("COMPU_METHOD");
+        }
         name_map.insert(old_name, new_name);
 
         module.compu_method[idx].unit = obfuscate_string_with_syms(&module.compu_method[idx].unit);
@@ -203,32 +444,54 @@ fn obfuscate_module(
         let old_name = module.compu_tab[idx].get_name().to_string();
         module
             .compu_tab
-            .rename_item(idx, &obfuscate_string(&old_name));
+            .rename_item(idx, &obfuscate_object_name(&old_name, keep_list));
         let new_name = module.compu_tab[idx].get_name().to_string();
+        if new_name != old_name {
+            report.add_renamed_object("COMPU_TAB");
+        }
         name_map.insert(old_name, new_name);
         module.compu_tab[idx].long_identifier =
             obfuscate_string(&module.compu_tab[idx].long_identifier);
     }
     for idx in 0..module.compu_vtab.len() {
         let old_name = module.compu_vtab[idx].get_name().to_string();
+        // if the values of the table match the values of an enum type, then the texts are replaced
+        // by the obfuscated enumerator names. Otherwise they are obfuscated like all other texts
+        let enumerators = vtab_enums.get(&old_name).and_then(|enums| {
+            enums
+                .iter()
+                .find(|enumerators| matches_vtab(&module.compu_vtab[idx], enumerators))
+        });
         module
             .compu_vtab
-            .rename_item(idx, &obfuscate_string(&old_name));
+            .rename_item(idx, &obfuscate_object_name(&old_name, keep_list));
         let new_name = module.compu_vtab[idx].get_name().to_string();
+        if new_name != old_name {
+            report.add_renamed_object("COMPU_VTAB");
+        }
         name_map.insert(old_name, new_name);
         module.compu_vtab[idx].long_identifier =
             obfuscate_string(&module.compu_vtab[idx].long_identifier);
 
         for value_pair in module.compu_vtab[idx].value_pairs.iter_mut() {
-            value_pair.out_val = obfuscate_string(&value_pair.out_val);
+            value_pair.out_val = if let Some(enumerators) = enumerators {
+                enumerator_name(enumerators, value_pair.in_val)
+                    .unwrap_or_default()
+                    .to_string()
+            } else {
+                obfuscate_string(&value_pair.out_val)
+            };
         }
     }
     for idx in 0..module.compu_vtab_range.len() {
         let old_name = module.compu_vtab_range[idx].get_name().to_string();
         module
             .compu_vtab_range
-            .rename_item(idx, &obfuscate_string(&old_name));
+            .rename_item(idx, &obfuscate_object_name(&old_name, keep_list));
         let new_name = module.compu_vtab_range[idx].get_name().to_string();
+        if new_name != old_name {
+            report.add_renamed_object("COMPU_VTAB_RANGE");
+        }
         name_map.insert(old_name, new_name);
         module.compu_vtab_range[idx].long_identifier =
             obfuscate_string(&module.compu_vtab_range[idx].long_identifier);
@@ -240,41 +503,120 @@ fn obfuscate_module(
     update_compu_tabs_xrefs(module, &name_map);
 }
 
-fn obfuscate_symbol_link(
+// shuffle the order in which the objects of the module are written
+fn shuffle_module(module: &mut a2lfile::Module, rng: &mut StdRng) {
+    shuffle_objectlist(&mut module.characteristic, rng);
+    shuffle_objectlist(&mut module.measurement, rng);
+    shuffle_objectlist(&mut module.axis_pts, rng);
+    shuffle_objectlist(&mut module.instance, rng);
+    shuffle_objectlist(&mut module.blob, rng);
+    shuffle_objectlist(&mut module.compu_method, rng);
+    shuffle_objectlist(&mut module.compu_tab, rng);
+    shuffle_objectlist(&mut module.compu_vtab, rng);
+    shuffle_objectlist(&mut module.compu_vtab_range, rng);
+    shuffle_objectlist(&mut module.typedef_structure, rng);
+    shuffle_objectlist(&mut module.typedef_characteristic, rng);
+    shuffle_objectlist(&mut module.typedef_measurement, rng);
+    shuffle_objectlist(&mut module.typedef_axis, rng);
+    shuffle_objectlist(&mut module.typedef_blob, rng);
+    shuffle_objectlist(&mut module.frame, rng);
+    shuffle_objectlist(&mut module.function, rng);
+    shuffle_objectlist(&mut module.group, rng);
+    shuffle_objectlist(&mut module.record_layout, rng);
+    shuffle_objectlist(&mut module.transformer, rng);
+    shuffle_objectlist(&mut module.unit, rng);
+}
+
+// the objects are written in the order of their uids; shuffling the uids shuffles the output
+fn shuffle_objectlist<T, U>(objects: &mut ItemList<T>, rng: &mut StdRng)
+where
+    T: A2lObject<U> + A2lObjectName,
+{
+    let mut uids: Vec<u32> = objects.iter().map(|obj| obj.get_layout().uid).collect();
+    uids.shuffle(rng);
+    for (obj, uid) in objects.iter_mut().zip(uids) {
+        obj.get_layout_mut().uid = uid;
+    }
+}
+
+// update the symbol name in a SYMBOL_LINK, and return the information about the symbol if it was found
+fn obfuscate_symbol_link<'dbg>(
     opt_symbol_link: &mut Option<a2lfile::SymbolLink>,
-    debuginfo: &debuginfo::DebugData,
+    object_name: &str,
+    debuginfo: &'dbg debuginfo::DebugData,
     dwarf_stringmapping: &HashMap<String, String>,
-) {
+    report: &mut Report,
+) -> Option<SymbolInfo<'dbg>> {
     if let Some(symbol_link) = opt_symbol_link {
-        if let Ok(sym_info) = find_symbol(&symbol_link.symbol_name, debuginfo, dwarf_stringmapping)
-        {
-            symbol_link.symbol_name = sym_info.name;
+        match find_symbol(&symbol_link.symbol_name, debuginfo, dwarf_stringmapping) {
+            Ok(sym_info) => {
+                symbol_link.symbol_name = sym_info.name.clone();
+                return Some(sym_info);
+            }
+            Err(error) => {
+                report.add_unresolved_symbol(&symbol_link.symbol_name, error);
+                report.add_unresolved_link(object_name, &symbol_link.symbol_name);
+            }
         }
     } else {
         *opt_symbol_link = None;
     }
+    None
+}
+
+// get the enumerators of a symbol with an enum type, or an array of enums
+fn get_enumerators<'dbg>(
+    sym_info: &SymbolInfo<'dbg>,
+    debuginfo: &'dbg debuginfo::DebugData,
+) -> Option<&'dbg [(String, i64)]> {
+    let mut typeinfo = sym_info.typeinfo.get_reference(&debuginfo.types);
+    if let debuginfo::DbgDataType::Array { arraytype, .. } = &typeinfo.datatype {
+        typeinfo = arraytype.get_reference(&debuginfo.types);
+    }
+    match &typeinfo.datatype {
+        debuginfo::DbgDataType::Enum { enumerators, .. } => Some(enumerators),
+        _ => None,
+    }
+}
+
+// every value in the COMPU_VTAB must be the value of one of the enumerators
+fn matches_vtab(compu_vtab: &a2lfile::CompuVtab, enumerators: &[(String, i64)]) -> bool {
+    !compu_vtab.value_pairs.is_empty()
+        && compu_vtab
+            .value_pairs
+            .iter()
+            .all(|value_pair| enumerator_name(enumerators, value_pair.in_val).is_some())
+}
+
+fn enumerator_name(enumerators: &[(String, i64)], value: f64) -> Option<&str> {
+    enumerators
+        .iter()
+        .find(|(_, enum_value)| *enum_value as f64 == value)
+        .map(|(name, _)| name.as_str())
 }
 
 fn obfuscate_ifdata(
     ifdata_vec: &mut Vec<a2lfile::IfData>,
+    object_name: &str,
     debuginfo: &debuginfo::DebugData,
     dwarf_stringmapping: &HashMap<String, String>,
+    report: &mut Report,
 ) {
     for ifdata in ifdata_vec {
-        if let Some(mut decoded_ifdata) = ifdata::A2mlVector::load_from_ifdata(ifdata) {
-            if let Some(canape_ext) = &mut decoded_ifdata.canape_ext {
-                if let Some(link_map) = &mut canape_ext.link_map {
-                    if let Ok(sym_info) =
-                        find_symbol(&link_map.symbol_name, debuginfo, dwarf_stringmapping)
-                    {
-                        link_map.symbol_name = sym_info.name;
-                    } else {
-                        // if the symbol is not found, we obfuscate it
-                        link_map.symbol_name = obfuscate_string(&link_map.symbol_name);
-                    }
-                    decoded_ifdata.store_to_ifdata(ifdata);
+        if let Some(mut decoded_ifdata) = ifdata::A2mlVector::load_from_ifdata(ifdata)
+            && let Some(canape_ext) = &mut decoded_ifdata.canape_ext
+            && let Some(link_map) = &mut canape_ext.link_map
+        {
+            match find_symbol(&link_map.symbol_name, debuginfo, dwarf_stringmapping) {
+                Ok(sym_info) => link_map.symbol_name = sym_info.name,
+                Err(error) => {
+                    report.add_unresolved_symbol(&link_map.symbol_name, error);
+                    // if the symbol is not found, we obfuscate it
+                    link_map.symbol_name = obfuscate_string(&link_map.symbol_name);
+                    report.add_unresolved_link(object_name, &link_map.symbol_name);
                 }
             }
+            decoded_ifdata.store_to_ifdata(ifdata);
         }
     }
 }
@@ -761,6 +1103,15 @@ fn obfuscate_string(input: &str) -> String {
     output
 }
 
+// obfuscate the name of an a2l object, unless it is on the keep list
+fn obfuscate_object_name(name: &str, keep_list: &KeepList) -> String {
+    if keep_list.contains(name) {
+        name.to_string()
+    } else {
+        obfuscate_string(name)
+    }
+}
+
 // weak obfuscation function that replaces all alphabetic characters with random ones
 // this is not a real obfuscation, but it is enough to make the string unreadable
 // non-alphabetic characters are not changed, so Abc.foo[33] will become Xyz.bar[33]
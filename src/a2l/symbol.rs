@@ -5,12 +5,13 @@ use std::collections::HashMap;
 #[derive(Clone)]
 pub(crate) struct SymbolInfo<'dbg> {
     pub(crate) name: String,
-    pub(crate) _address: u64,
-    pub(crate) _typeinfo: &'dbg TypeInfo,
+    pub(crate) address: u64,
+    pub(crate) typeinfo: &'dbg TypeInfo,
     pub(crate) _unit_idx: usize,
     pub(crate) _function_name: &'dbg Option<String>,
     pub(crate) _namespaces: &'dbg [String],
     pub(crate) _is_unique: bool,
+    pub(crate) var_dbginfo_offset: usize,
 }
 
 struct AdditionalSpec {
@@ -29,21 +30,52 @@ pub(crate) fn find_symbol<'a>(
     // The varname in a symbol link might contain additional information
     // var{Function:FuncName}{CompileUnit:UnitName_c}{Namespace:Global}"
     // This allows variables that occur in multiple files / functions / namespaces to be identified correctly
-    let (plain_symbol, additional_spec) = get_additional_spec(varname);
+    let (plain_symbol, mut additional_spec) = get_additional_spec(varname);
 
     // split the a2l symbol name: e.g. "motortune.param._0_" -> ["motortune", "param", "_0_"]
-    let components = split_symbol_components(plain_symbol);
-    let components = components
+    let original_components = split_symbol_components(plain_symbol);
+    // map the original names in the symbol link to the already obfuscated names in the debug data
+    let mapped_components = original_components
         .iter()
-        .map(|s| {
-            // map the original name in the symbol link to the already obfuscated name in the debug data
-            if let Some(mapped) = dwarf_stringmapping.get(*s) {
-                mapped.as_str()
-            } else {
-                *s
-            }
-        })
+        .map(|s| map_qualified_name(s, dwarf_stringmapping))
+        .collect::<Vec<_>>();
+    let mut components = mapped_components
+        .iter()
+        .map(String::as_str)
         .collect::<Vec<_>>();
+
+    if let Some(spec) = &mut additional_spec {
+        // variables that had the same name in different compile units may have been renamed when the units were merged
+        if let Some(unit_name) = &spec.simple_unit_name
+            && let Some(mapped) = dwarf_stringmapping.get(&format!(
+                "{}{{CompileUnit:{unit_name}}}",
+                original_components[0]
+            ))
+        {
+            components[0] = mapped.as_str();
+        }
+        // units can be renamed, too
+        if let Some(unit_name) = &mut spec.simple_unit_name
+            && let Some(mapped) = dwarf_stringmapping.get(&format!("{{CompileUnit:{unit_name}}}"))
+        {
+            *unit_name = mapped.clone();
+        }
+        // a merged unit has no relation to the unit names in the qualifier
+        if debug_data.unit_names.len() == 1 {
+            spec.simple_unit_name = None;
+        }
+        // the function and namespace names in the debug data are obfuscated, too
+        if let Some(function_name) = &mut spec.function_name
+            && let Some(mapped) = dwarf_stringmapping.get(function_name.as_str())
+        {
+            *function_name = mapped.clone();
+        }
+        for namespace in &mut spec.namespaces {
+            if let Some(mapped) = dwarf_stringmapping.get(namespace.as_str()) {
+                *namespace = mapped.clone();
+            }
+        }
+    }
     let mut obfuscated_symbol = String::with_capacity(plain_symbol.len());
     obfuscated_symbol.push_str(components[0]);
     if components.len() > 1 {
@@ -54,6 +86,10 @@ pub(crate) fn find_symbol<'a>(
             obfuscated_symbol.push_str(component);
         }
     }
+    obfuscated_symbol.push_str(&obfuscate_additional_spec(
+        &varname[plain_symbol.len()..],
+        dwarf_stringmapping,
+    ));
 
     // find the symbol in the symbol table
     match find_symbol_from_components(&components, &additional_spec, debug_data) {
@@ -62,7 +98,6 @@ pub(crate) fn find_symbol<'a>(
             ..sym_info
         }),
         Err(find_err) => {
-            println!("find_symbol_from_components failed: {}", find_err);
             // it was not found using the given varname; if this is name has a mangled form then try that instead
             if let Some(mangled) = debug_data.demangled_names.get(components[0]) {
                 let mut components_mangled = components.clone();
@@ -71,7 +106,7 @@ pub(crate) fn find_symbol<'a>(
                     find_symbol_from_components(&components_mangled, &additional_spec, debug_data)
                 {
                     let mangled_varname =
-                        mangled.to_owned() + varname.strip_prefix(components[0]).unwrap();
+                        mangled.to_owned() + obfuscated_symbol.strip_prefix(components[0]).unwrap();
                     return Ok(SymbolInfo {
                         name: mangled_varname,
                         ..sym_info
@@ -102,12 +137,13 @@ fn find_symbol_from_components<'a>(
             find_membertype(vartype, debug_data, components, 1, varinfo.address).map(
                 |(addr, typeinfo)| SymbolInfo {
                     name: "".to_string(),
-                    _address: addr,
-                    _typeinfo: typeinfo,
+                    address: addr,
+                    typeinfo,
                     _unit_idx: varinfo.unit_idx,
                     _function_name: &varinfo.function,
                     _namespaces: &varinfo.namespaces,
                     _is_unique: is_unique,
+                    var_dbginfo_offset: varinfo.dbginfo_offset,
                 },
             )
         } else {
@@ -116,8 +152,8 @@ fn find_symbol_from_components<'a>(
             if components.len() == 1 {
                 Ok(SymbolInfo {
                     name: "".to_string(),
-                    _address: varinfo.address,
-                    _typeinfo: &TypeInfo {
+                    address: varinfo.address,
+                    typeinfo: &TypeInfo {
                         datatype: DbgDataType::Uint8,
                         name: None,
                         unit_idx: usize::MAX,
@@ -127,6 +163,7 @@ fn find_symbol_from_components<'a>(
                     _namespaces: &varinfo.namespaces,
                     _function_name: &None,
                     _is_unique: is_unique,
+                    var_dbginfo_offset: varinfo.dbginfo_offset,
                 })
             } else {
                 Err(format!(
@@ -198,6 +235,55 @@ fn get_additional_spec(varname_ext: &str) -> (&str, Option<AdditionalSpec>) {
     }
 }
 
+// map a name to its obfuscated version. C++ names like "ns::Class::var" are mapped component by component,
+// which matches the demangled version of the obfuscated mangled name
+fn map_qualified_name(name: &str, dwarf_stringmapping: &HashMap<String, String>) -> String {
+    if let Some(mapped) = dwarf_stringmapping.get(name) {
+        mapped.clone()
+    } else if name.contains("::") {
+        name.split("::")
+            .map(|part| dwarf_stringmapping.get(part).map_or(part, String::as_str))
+            .collect::<Vec<_>>()
+            .join("::")
+    } else {
+        name.to_string()
+    }
+}
+
+// update the names in the additional spec of a symbol link to match the obfuscated debug info
+// e.g. "{Function:FuncName}{CompileUnit:UnitName_c}{Namespace:Global}" -> "{Function:xYz12abc}{CompileUnit:lokari_c}{Namespace:Global}"
+fn obfuscate_additional_spec(
+    spec_str: &str,
+    dwarf_stringmapping: &HashMap<String, String>,
+) -> String {
+    let mut obfuscated_spec = String::with_capacity(spec_str.len());
+    for component in spec_str.split_inclusive('}') {
+        let Some((kind, name)) = component
+            .strip_prefix('{')
+            .and_then(|c| c.strip_suffix('}'))
+            .and_then(|c| c.split_once(':'))
+        else {
+            obfuscated_spec.push_str(component);
+            continue;
+        };
+        let mapped = if kind == "CompileUnit" {
+            let mapped = dwarf_stringmapping.get(&format!("{{CompileUnit:{name}}}"));
+            if mapped.is_none() {
+                // the unit has no name in the obfuscated debug info, so the original name would only leak information
+                continue;
+            }
+            mapped
+        } else {
+            dwarf_stringmapping.get(name)
+        };
+        obfuscated_spec.push_str(&format!(
+            "{{{kind}:{}}}",
+            mapped.map_or(name, String::as_str)
+        ));
+    }
+    obfuscated_spec
+}
+
 // split the symbol into components
 // e.g. "my_struct.array_field[5][6]" -> [ "my_struct", "array_field", "[5]", "[6]" ]
 fn split_symbol_components(varname: &str) -> Vec<&str> {
@@ -339,10 +425,7 @@ fn get_index(idxstr: &str) -> Option<usize> {
         || (idxstr.starts_with('[') && idxstr.ends_with(']'))
     {
         let idxstrlen = idxstr.len();
-        match idxstr[1..(idxstrlen - 1)].parse() {
-            Ok(val) => Some(val),
-            Err(_) => None,
-        }
+        idxstr[1..(idxstrlen - 1)].parse().ok()
     } else {
         None
     }
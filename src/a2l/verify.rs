@@ -0,0 +1,275 @@
+use super::debuginfo::{DebugData, TypeComparison};
+use super::{ifdata, linked_symbols, symbol::find_symbol};
+use crate::options::Options;
+use crate::report::Report;
+use a2lfile::{A2lObjectName, Module};
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
+
+/// reload the output elf and a2l files and check that they still fit together:
+/// every SYMBOL_LINK and IF_DATA LINK_MAP must resolve, each linked variable must have the
+/// same type shape as in the input, object names must be unique, and the a2l file must pass its
+/// consistency check. Returns a description of each violation
+pub(crate) fn verify_output(
+    options: &Options,
+    dwarf_stringmapping: &HashMap<String, String>,
+    report: &mut Report,
+) -> Result<Vec<String>, String> {
+    let a2l_in = load_a2l(&options.a2l_in)?;
+    let a2l_out = load_a2l(&options.a2l_out)?;
+    let debug_in = DebugData::load_dwarf(&options.elf_in, &options.split_dwarf)?;
+    report.add_debug_info_errors(&debug_in.load_errors);
+    // the obfuscated elf file never contains split debug info
+    let debug_out = DebugData::load_dwarf(&options.elf_out, &[])?;
+    report.add_debug_info_errors(&debug_out.load_errors);
+
+    let mut violations: Vec<String> = a2l_out
+        .check()
+        .iter()
+        .map(|error| format!("a2l check: {error}"))
+        .collect();
+
+    for module in &a2l_out.project.module {
+        check_unique_names(module, &mut violations);
+    }
+
+    // links that are already broken in the input are passed through, they are not reported again
+    let no_mapping = HashMap::new();
+    let unresolved_in: HashSet<String> = a2l_in
+        .project
+        .module
+        .iter()
+        .flat_map(linked_symbols)
+        .filter(|(_, symbol_name)| find_symbol(symbol_name, &debug_in, &no_mapping).is_err())
+        .map(|(_, symbol_name)| symbol_name)
+        .collect();
+
+    let mut linked_out = HashSet::new();
+    for module in &a2l_out.project.module {
+        check_output_links(
+            module,
+            &debug_out,
+            &unresolved_in,
+            report,
+            &mut linked_out,
+            &mut violations,
+        );
+    }
+
+    // each variable that the input a2l file refers to must still be referenced, and keep its type
+    let context = TypeComparison {
+        types: &debug_in.types,
+        other_types: &debug_out.types,
+        name_map: dwarf_stringmapping,
+    };
+    let mut checked = HashSet::new();
+    for module in &a2l_in.project.module {
+        for (object_name, symbol_name) in linked_symbols(module) {
+            let Ok(sym_in) = find_symbol(&symbol_name, &debug_in, &no_mapping) else {
+                continue;
+            };
+            if !checked.insert(symbol_name.clone()) {
+                continue;
+            }
+            let Ok(sym_out) = find_symbol(&symbol_name, &debug_out, dwarf_stringmapping) else {
+                violations.push(format!(
+                    "{object_name}: the symbol {symbol_name} was lost in the output elf file"
+                ));
+                continue;
+            };
+            if !linked_out.contains(&sym_out.name) {
+                violations.push(format!(
+                    "{object_name}: no object in the output a2l file refers to the symbol {symbol_name} (now {})",
+                    sym_out.name
+                ));
+            }
+            if !sym_in.typeinfo.compare(sym_out.typeinfo, &context) {
+                violations.push(format!(
+                    "{object_name}: the type of the symbol {symbol_name} (now {}) has changed",
+                    sym_out.name
+                ));
+            }
+        }
+    }
+
+    Ok(violations)
+}
+
+// every link in the output a2l file must resolve in the output elf file. Links that could not be
+// resolved during the obfuscation are skipped: a SYMBOL_LINK keeps its name, which is also broken in
+// the input, while a LINK_MAP gets a random name, so it is identified by its object instead.
+// The symbol names of all links are collected in linked_out
+fn check_output_links(
+    module: &Module,
+    debug_out: &DebugData,
+    unresolved_in: &HashSet<String>,
+    report: &Report,
+    linked_out: &mut HashSet<String>,
+    violations: &mut Vec<String>,
+) {
+    let no_mapping = HashMap::new();
+    for (object_name, symbol_name) in linked_symbols(module) {
+        if find_symbol(&symbol_name, debug_out, &no_mapping).is_err()
+            && !unresolved_in.contains(&symbol_name)
+            && !report.is_unresolved_link(object_name, &symbol_name)
+        {
+            violations.push(format!(
+                "{object_name}: the symbol {symbol_name} does not exist in the output elf file"
+            ));
+        }
+        linked_out.insert(symbol_name);
+    }
+}
+
+fn load_a2l(filename: &OsStr) -> Result<a2lfile::A2lFile, String> {
+    let (a2l, _) = a2lfile::load(filename, Some(ifdata::A2MLVECTOR_TEXT.to_string()), false)
+        .map_err(|e| e.to_string())?;
+    Ok(a2l)
+}
+
+// the obfuscated names are random, so two objects could end up with the same name.
+// Each group of object types shares a namespace in the module
+fn check_unique_names(module: &Module, violations: &mut Vec<String>) {
+    let namespaces: [(&str, Vec<&str>); 8] = [
+        (
+            "CHARACTERISTIC / MEASUREMENT / AXIS_PTS / BLOB / INSTANCE",
+            module
+                .characteristic
+                .iter()
+                .map(|item| item.get_name())
+                .chain(module.measurement.iter().map(|item| item.get_name()))
+                .chain(module.axis_pts.iter().map(|item| item.get_name()))
+                .chain(module.blob.iter().map(|item| item.get_name()))
+                .chain(module.instance.iter().map(|item| item.get_name()))
+                .collect(),
+        ),
+        (
+            "COMPU_METHOD",
+            module
+                .compu_method
+                .iter()
+                .map(|item| item.get_name())
+                .collect(),
+        ),
+        (
+            "COMPU_TAB / COMPU_VTAB / COMPU_VTAB_RANGE",
+            module
+                .compu_tab
+                .iter()
+                .map(|item| item.get_name())
+                .chain(module.compu_vtab.iter().map(|item| item.get_name()))
+                .chain(module.compu_vtab_range.iter().map(|item| item.get_name()))
+                .collect(),
+        ),
+        (
+            "RECORD_LAYOUT",
+            module
+                .record_layout
+                .iter()
+                .map(|item| item.get_name())
+                .collect(),
+        ),
+        (
+            "FUNCTION",
+            module.function.iter().map(|item| item.get_name()).collect(),
+        ),
+        (
+            "GROUP",
+            module.group.iter().map(|item| item.get_name()).collect(),
+        ),
+        (
+            "UNIT",
+            module.unit.iter().map(|item| item.get_name()).collect(),
+        ),
+        (
+            "TYPEDEF",
+            module
+                .typedef_structure
+                .iter()
+                .map(|item| item.get_name())
+                .chain(
+                    module
+                        .typedef_characteristic
+                        .iter()
+                        .map(|item| item.get_name()),
+                )
+                .chain(
+                    module
+                        .typedef_measurement
+                        .iter()
+                        .map(|item| item.get_name()),
+                )
+                .chain(module.typedef_axis.iter().map(|item| item.get_name()))
+                .chain(module.typedef_blob.iter().map(|item| item.get_name()))
+                .collect(),
+        ),
+    ];
+
+    for (kind, names) in namespaces {
+        let mut seen = HashSet::new();
+        let mut reported = HashSet::new();
+        for name in names {
+            if !seen.insert(name) && reported.insert(name) {
+                violations.push(format!(
+                    "module {}: the name {name} is used by more than one {kind}",
+                    module.get_name()
+                ));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // a module with two MEASUREMENTs, each with a LINK_MAP to a symbol that does not exist
+    const A2L_TEXT: &str = r#"
+ASAP2_VERSION 1 71
+/begin PROJECT Project ""
+  /begin MODULE Module ""
+    /begin MEASUREMENT renamedObject "" UBYTE NO_COMPU_METHOD 0 0 0 255
+      /begin IF_DATA CANAPE_EXT 100 LINK_MAP "randomLinkName" 0x0 0 0 0 1 0x87 0 /end IF_DATA
+    /end MEASUREMENT
+    /begin MEASUREMENT brokenObject "" UBYTE NO_COMPU_METHOD 0 0 0 255
+      /begin IF_DATA CANAPE_EXT 100 LINK_MAP "lostLinkName" 0x0 0 0 0 1 0x87 0 /end IF_DATA
+    /end MEASUREMENT
+  /end MODULE
+/end PROJECT
+"#;
+
+    #[test]
+    fn unresolved_link_map() {
+        let (a2l, _) =
+            a2lfile::load_from_string(A2L_TEXT, Some(ifdata::A2MLVECTOR_TEXT.to_string()), false)
+                .unwrap();
+        let debug_out = DebugData {
+            variables: Default::default(),
+            types: HashMap::new(),
+            _typenames: HashMap::new(),
+            demangled_names: HashMap::new(),
+            unit_names: vec![],
+            _sections: HashMap::new(),
+            load_errors: vec![],
+        };
+        // the first LINK_MAP could not be resolved during the obfuscation and got a random name
+        let mut report = Report::default();
+        report.add_unresolved_link("renamedObject", "randomLinkName");
+
+        let mut linked_out = HashSet::new();
+        let mut violations = vec![];
+        check_output_links(
+            &a2l.project.module[0],
+            &debug_out,
+            &HashSet::new(),
+            &report,
+            &mut linked_out,
+            &mut violations,
+        );
+        assert_eq!(
+            violations,
+            ["brokenObject: the symbol lostLinkName does not exist in the output elf file"]
+        );
+        assert_eq!(linked_out.len(), 2);
+    }
+}
@@ -0,0 +1,168 @@
+use crate::a2l::{CalibrationObject, ValueType};
+use crate::layout::AddressLayout;
+use crate::options::CalibrationData;
+use object::Endian;
+use object::build::elf::{Builder, SectionData};
+use rand::{RngExt, rngs::StdRng};
+
+/// Replace the content of the kept data sections: the bytes of the calibration objects get new
+/// values according to the mode, and all other bytes are set to zero.
+/// The data sections were moved by the address randomization, so the calibration objects are
+/// found at their new addresses
+pub(crate) fn scramble_calibration_data(
+    elf_builder: &mut Builder<'_>,
+    objects: &[CalibrationObject],
+    address_layout: &AddressLayout,
+    mode: CalibrationData,
+    rng: &mut StdRng,
+) {
+    let endian = elf_builder.endian;
+    for sec in &mut elf_builder.sections {
+        if !sec.is_alloc() {
+            continue;
+        }
+        let SectionData::Data(data) = &sec.data else {
+            continue;
+        };
+        let mut content = vec![0u8; data.len()];
+        for object in objects {
+            let Some(offset) = address_layout
+                .translate(object.address)
+                .and_then(|address| address.checked_sub(sec.sh_addr))
+                .and_then(|offset| usize::try_from(offset).ok())
+                .filter(|offset| *offset < content.len())
+            else {
+                continue;
+            };
+            let end = (offset + object.size as usize).min(content.len());
+            fill_values(&mut content[offset..end], object, mode, endian, rng);
+        }
+        sec.data = SectionData::Data(content.into());
+    }
+}
+
+fn fill_values(
+    bytes: &mut [u8],
+    object: &CalibrationObject,
+    mode: CalibrationData,
+    endian: object::Endianness,
+    rng: &mut StdRng,
+) {
+    match mode {
+        CalibrationData::Remove | CalibrationData::Zero => bytes.fill(0),
+        CalibrationData::Random => rng.fill(bytes),
+        CalibrationData::Limits => {
+            // without a value type or usable limits, there is no value that is known to be valid
+            let (Some(value_type), Some((lower, upper))) = (object.value_type, object.limits)
+            else {
+                bytes.fill(0);
+                return;
+            };
+            let value_size = match value_type {
+                ValueType::Unsigned(size) | ValueType::Signed(size) | ValueType::Float(size) => {
+                    size as usize
+                }
+            };
+            for value_bytes in bytes.chunks_exact_mut(value_size) {
+                let value = if lower < upper && (upper - lower).is_finite() {
+                    rng.random_range(lower..=upper)
+                } else {
+                    lower
+                };
+                encode_value(value_bytes, value, value_type, endian);
+            }
+        }
+    }
+}
+
+// store a value in the format of the value type. Integer values are rounded and
+// limited to the range of the type
+fn encode_value(bytes: &mut [u8], value: f64, value_type: ValueType, endian: object::Endianness) {
+    match value_type {
+        ValueType::Float(4) => bytes.copy_from_slice(&endian.write_u32((value as f32).to_bits())),
+        ValueType::Float(_) => bytes.copy_from_slice(&endian.write_u64(value.to_bits())),
+        ValueType::Unsigned(size) => {
+            let max = u64::MAX >> (64 - size * 8);
+            // the conversion from f64 saturates at 0 and u64::MAX
+            let value = (value.round() as u64).min(max);
+            write_integer(bytes, value, endian);
+        }
+        ValueType::Signed(size) => {
+            let max = i64::MAX >> (64 - size * 8);
+            let value = (value.round() as i64).clamp(-max - 1, max);
+            write_integer(bytes, value as u64, endian);
+        }
+    }
+}
+
+// write the lower bytes of the value
+fn write_integer(bytes: &mut [u8], value: u64, endian: object::Endianness) {
+    match bytes.len() {
+        1 => bytes[0] = value as u8,
+        2 => bytes.copy_from_slice(&endian.write_u16(value as u16)),
+        4 => bytes.copy_from_slice(&endian.write_u32(value as u32)),
+        _ => bytes.copy_from_slice(&endian.write_u64(value)),
+    }
+}
+
+/// find the EPK string in the allocated sections of the input file. The address from the a2l file
+/// is used if the string is found there, otherwise the first occurrence of the string is used
+pub(crate) fn find_epk(
+    elf_builder: &Builder<'_>,
+    identifier: &str,
+    a2l_address: Option<u64>,
+) -> Option<u64> {
+    let identifier = identifier.as_bytes();
+    let section_data = elf_builder
+        .sections
+        .iter()
+        .filter_map(|sec| match &sec.data {
+            SectionData::Data(data) if sec.is_alloc() && !identifier.is_empty() => {
+                Some((sec.sh_addr, &**data))
+            }
+            _ => None,
+        });
+    let mut first_match = None;
+    for (sh_addr, data) in section_data {
+        if let Some(address) = a2l_address
+            && let Some(offset) = address
+                .checked_sub(sh_addr)
+                .and_then(|offset| usize::try_from(offset).ok())
+            && data
+                .get(offset..)
+                .is_some_and(|bytes| bytes.starts_with(identifier))
+        {
+            return Some(address);
+        }
+        if first_match.is_none() {
+            first_match = data
+                .windows(identifier.len())
+                .position(|window| window == identifier)
+                .map(|offset| sh_addr + offset as u64);
+        }
+    }
+    first_match
+}
+
+/// write the new EPK string to its (randomized) address in the kept data sections
+pub(crate) fn write_epk(elf_builder: &mut Builder<'_>, address: u64, identifier: &str) {
+    for sec in &mut elf_builder.sections {
+        if !sec.is_alloc() {
+            continue;
+        }
+        let SectionData::Data(data) = &mut sec.data else {
+            continue;
+        };
+        if let Some(offset) = address
+            .checked_sub(sec.sh_addr)
+            .and_then(|offset| usize::try_from(offset).ok())
+            && let Some(bytes) = data
+                .to_mut()
+                .get_mut(offset..)
+                .and_then(|bytes| bytes.get_mut(..identifier.len()))
+        {
+            bytes.copy_from_slice(identifier.as_bytes());
+            return;
+        }
+    }
+}
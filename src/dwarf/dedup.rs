@@ -0,0 +1,317 @@
+use super::SliceType;
+use crate::a2l::debuginfo::{TypeComparison, load_types};
+use crate::report::Report;
+use gimli::{DwAt, DwTag, Dwarf, Reader, Section};
+use std::collections::{HashMap, HashSet};
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+/// a debug info entry, reduced to the information that is relevant for a structural comparison
+struct TypeNode {
+    tag: DwTag,
+    parent: Option<usize>,
+    name: Option<Vec<u8>>,
+    attributes: Vec<(DwAt, AttrKey)>,
+    references: Vec<(DwAt, usize)>,
+    children: Vec<usize>,
+}
+
+/// attribute values in a form that can be compared across units
+#[derive(PartialEq, Eq, Hash)]
+enum AttrKey {
+    Str(Vec<u8>),
+    Udata(u64),
+    Sdata(i64),
+    Bytes(Vec<u8>),
+    Other(String),
+}
+
+struct HashState {
+    memo: HashMap<usize, u64>,
+    stack: Vec<usize>,
+}
+
+/// find types that are structurally identical to a type that occurs earlier in the debug info.
+/// The result maps the debug info offset of each duplicate entry (including all entries inside
+/// the duplicate type) to the offset of the corresponding entry in the first occurrence.
+pub(super) fn find_duplicate_types(
+    input_dwarf: &Dwarf<SliceType>,
+    retained_entries: Option<&HashSet<usize>>,
+    report: &mut Report,
+) -> HashMap<usize, usize> {
+    let nodes = collect_type_nodes(input_dwarf);
+
+    // only types that are declared directly in a unit or in a namespace are deduplicated;
+    // types nested inside other types are handled as part of their container
+    let mut candidates: Vec<usize> = nodes
+        .iter()
+        .filter(|(offset, node)| {
+            is_type_tag(node.tag)
+                && node
+                    .parent
+                    .and_then(|parent| nodes.get(&parent))
+                    .is_some_and(|parent| {
+                        parent.tag == gimli::constants::DW_TAG_compile_unit
+                            || parent.tag == gimli::constants::DW_TAG_partial_unit
+                            || parent.tag == gimli::constants::DW_TAG_namespace
+                    })
+                && retained_entries.is_none_or(|retained| retained.contains(offset))
+        })
+        .map(|(offset, _)| *offset)
+        .collect();
+    // the first occurrence of each type becomes the canonical version
+    candidates.sort_unstable();
+
+    let mut state = HashState {
+        memo: HashMap::new(),
+        stack: Vec::new(),
+    };
+    // the types are compared in the same way as the types of the variables before and after obfuscation
+    let endian = if super::version::is_big_endian(input_dwarf) {
+        object::Endianness::Big
+    } else {
+        object::Endianness::Little
+    };
+    let (types, load_errors) = load_types(reader_dwarf(input_dwarf), endian, &candidates);
+    report.add_debug_info_errors(&load_errors);
+    let no_mapping = HashMap::new();
+    let comparison = TypeComparison {
+        types: &types,
+        other_types: &types,
+        name_map: &no_mapping,
+    };
+
+    // structural hash -> canonical types with this hash
+    let mut canonical_types = HashMap::<u64, Vec<usize>>::new();
+    let mut duplicates = HashMap::<usize, usize>::new();
+    for offset in candidates {
+        let (hash, _) = structural_hash(&nodes, offset, &mut state);
+        let same_hash = canonical_types.entry(hash).or_default();
+        // the hash covers the names and all attributes; different types can have the same hash,
+        // so the final decision is made by comparing the layout of the types
+        if let Some(canonical) = same_hash.iter().find(|canonical| {
+            types.get(canonical).zip(types.get(&offset)).is_some_and(
+                |(canonical_type, typeinfo)| canonical_type.compare(typeinfo, &comparison),
+            )
+        }) {
+            map_subtree(&nodes, offset, *canonical, &mut duplicates);
+        } else {
+            same_hash.push(offset);
+        }
+    }
+
+    duplicates
+}
+
+// the type reader needs its own Dwarf, which refers to the same section data
+fn reader_dwarf<'data>(input_dwarf: &Dwarf<SliceType<'data>>) -> Dwarf<SliceType<'data>> {
+    let empty = SliceType::new(&[], input_dwarf.debug_info.reader().endian());
+    let Ok(dwarf) = Dwarf::load(|id| -> Result<_, std::convert::Infallible> {
+        Ok(match id {
+            gimli::SectionId::DebugAbbrev => *input_dwarf.debug_abbrev.reader(),
+            gimli::SectionId::DebugAddr => *input_dwarf.debug_addr.reader(),
+            gimli::SectionId::DebugInfo => *input_dwarf.debug_info.reader(),
+            gimli::SectionId::DebugLineStr => *input_dwarf.debug_line_str.reader(),
+            gimli::SectionId::DebugStr => *input_dwarf.debug_str.reader(),
+            gimli::SectionId::DebugStrOffsets => *input_dwarf.debug_str_offsets.reader(),
+            _ => empty,
+        })
+    });
+    dwarf
+}
+
+// read all entries of all units into a map of debuginfo offset -> TypeNode
+fn collect_type_nodes(input_dwarf: &Dwarf<SliceType>) -> HashMap<usize, TypeNode> {
+    let mut nodes = HashMap::<usize, TypeNode>::new();
+
+    let mut unit_iter = input_dwarf.debug_info.units();
+    while let Ok(Some(unit_header)) = unit_iter.next() {
+        let Ok(unit) = input_dwarf.unit(unit_header) else {
+            continue;
+        };
+        let mut parents = Vec::<usize>::new();
+        let mut entries_cursor = unit.entries();
+        while let Ok(Some(entry)) = entries_cursor.next_dfs() {
+            let offset = entry.offset().to_debug_info_offset(&unit.header).unwrap().0;
+            parents.truncate(entry.depth() as usize);
+            let parent = parents.last().copied();
+            parents.push(offset);
+
+            let mut name = None;
+            let mut attributes = Vec::new();
+            let mut references = Vec::new();
+            for attr in entry.attrs() {
+                match (attr.name(), attr.value()) {
+                    // these attributes don't change the meaning of a type; the file index in
+                    // DW_AT_decl_file is even different in every unit for the same header file
+                    (
+                        gimli::constants::DW_AT_sibling
+                        | gimli::constants::DW_AT_decl_file
+                        | gimli::constants::DW_AT_decl_line
+                        | gimli::constants::DW_AT_decl_column,
+                        _,
+                    ) => {}
+                    (gimli::constants::DW_AT_name, value) => {
+                        name = input_dwarf
+                            .attr_string(&unit, value)
+                            .ok()
+                            .map(|s| s.slice().to_vec());
+                    }
+                    (attrtype, gimli::AttributeValue::UnitRef(unit_offset)) => {
+                        if let Some(target) = unit_offset.to_debug_info_offset(&unit.header) {
+                            references.push((attrtype, target.0));
+                        }
+                    }
+                    (attrtype, gimli::AttributeValue::DebugInfoRef(target)) => {
+                        references.push((attrtype, target.0));
+                    }
+                    (attrtype, value) => {
+                        attributes.push((attrtype, attr_key(input_dwarf, &unit, value)));
+                    }
+                }
+            }
+
+            if let Some(parent_offset) = parent
+                && let Some(parent_node) = nodes.get_mut(&parent_offset)
+            {
+                parent_node.children.push(offset);
+            }
+            nodes.insert(
+                offset,
+                TypeNode {
+                    tag: entry.tag(),
+                    parent,
+                    name,
+                    attributes,
+                    references,
+                    children: Vec::new(),
+                },
+            );
+        }
+    }
+
+    nodes
+}
+
+fn attr_key(
+    input_dwarf: &Dwarf<SliceType>,
+    unit: &gimli::Unit<SliceType>,
+    value: gimli::AttributeValue<SliceType>,
+) -> AttrKey {
+    match value {
+        gimli::AttributeValue::String(_)
+        | gimli::AttributeValue::DebugStrRef(_)
+        | gimli::AttributeValue::DebugLineStrRef(_)
+        | gimli::AttributeValue::DebugStrOffsetsIndex(_) => input_dwarf
+            .attr_string(unit, value)
+            .map(|s| AttrKey::Str(s.slice().to_vec()))
+            .unwrap_or_else(|_| AttrKey::Other(format!("{value:?}"))),
+        gimli::AttributeValue::Block(data) => AttrKey::Bytes(data.slice().to_vec()),
+        gimli::AttributeValue::Exprloc(expression) => AttrKey::Bytes(expression.0.slice().to_vec()),
+        gimli::AttributeValue::Sdata(val) => AttrKey::Sdata(val),
+        gimli::AttributeValue::Flag(flag) => AttrKey::Udata(u64::from(flag)),
+        _ => match value.udata_value() {
+            Some(val) => AttrKey::Udata(val),
+            None => AttrKey::Other(format!("{value:?}")),
+        },
+    }
+}
+
+// compute a hash over the structure of an entry, its children and all entries it references.
+// Recursive types are handled by hashing the distance to the entry on the stack instead of following the reference again.
+// The second return value is the lowest stack position that was referenced in this way; if it is above
+// the position of the current entry, the hash does not depend on the path that led here and can be cached.
+fn structural_hash(
+    nodes: &HashMap<usize, TypeNode>,
+    offset: usize,
+    state: &mut HashState,
+) -> (u64, usize) {
+    if let Some(hash) = state.memo.get(&offset) {
+        return (*hash, usize::MAX);
+    }
+    let mut hasher = DefaultHasher::new();
+    if let Some(pos) = state.stack.iter().position(|item| *item == offset) {
+        (state.stack.len() - pos).hash(&mut hasher);
+        return (hasher.finish(), pos);
+    }
+    let Some(node) = nodes.get(&offset) else {
+        return (0, usize::MAX);
+    };
+
+    let position = state.stack.len();
+    state.stack.push(offset);
+    let mut lowest = usize::MAX;
+
+    node.tag.0.hash(&mut hasher);
+    node.name.hash(&mut hasher);
+    node.attributes.hash(&mut hasher);
+    scope_names(nodes, offset).hash(&mut hasher);
+    for (attrtype, target) in &node.references {
+        let (hash, low) = structural_hash(nodes, *target, state);
+        attrtype.0.hash(&mut hasher);
+        hash.hash(&mut hasher);
+        lowest = lowest.min(low);
+    }
+    for child in &node.children {
+        let (hash, low) = structural_hash(nodes, *child, state);
+        hash.hash(&mut hasher);
+        lowest = lowest.min(low);
+    }
+
+    state.stack.pop();
+    let hash = hasher.finish();
+    if lowest >= position {
+        state.memo.insert(offset, hash);
+    }
+    (hash, lowest)
+}
+
+// the names of the namespaces and types that contain an entry, so that e.g. a::T and b::T are never merged
+fn scope_names(nodes: &HashMap<usize, TypeNode>, offset: usize) -> Vec<&[u8]> {
+    let mut names = Vec::new();
+    let mut parent = nodes.get(&offset).and_then(|node| node.parent);
+    while let Some(parent_node) = parent.and_then(|parent_offset| nodes.get(&parent_offset)) {
+        if parent_node.tag == gimli::constants::DW_TAG_compile_unit
+            || parent_node.tag == gimli::constants::DW_TAG_partial_unit
+        {
+            break;
+        }
+        names.push(parent_node.name.as_deref().unwrap_or_default());
+        parent = parent_node.parent;
+    }
+    names
+}
+
+// record that the duplicate entry and all entries inside it are replaced by the matching canonical entries
+fn map_subtree(
+    nodes: &HashMap<usize, TypeNode>,
+    duplicate: usize,
+    canonical: usize,
+    duplicates: &mut HashMap<usize, usize>,
+) {
+    duplicates.insert(duplicate, canonical);
+    if let (Some(dup_node), Some(canon_node)) = (nodes.get(&duplicate), nodes.get(&canonical)) {
+        for (dup_child, canon_child) in dup_node.children.iter().zip(&canon_node.children) {
+            map_subtree(nodes, *dup_child, *canon_child, duplicates);
+        }
+    }
+}
+
+fn is_type_tag(tag: DwTag) -> bool {
+    tag == gimli::constants::DW_TAG_base_type
+        || tag == gimli::constants::DW_TAG_typedef
+        || tag == gimli::constants::DW_TAG_pointer_type
+        || tag == gimli::constants::DW_TAG_reference_type
+        || tag == gimli::constants::DW_TAG_rvalue_reference_type
+        || tag == gimli::constants::DW_TAG_ptr_to_member_type
+        || tag == gimli::constants::DW_TAG_const_type
+        || tag == gimli::constants::DW_TAG_volatile_type
+        || tag == gimli::constants::DW_TAG_restrict_type
+        || tag == gimli::constants::DW_TAG_atomic_type
+        || tag == gimli::constants::DW_TAG_structure_type
+        || tag == gimli::constants::DW_TAG_class_type
+        || tag == gimli::constants::DW_TAG_union_type
+        || tag == gimli::constants::DW_TAG_enumeration_type
+        || tag == gimli::constants::DW_TAG_array_type
+        || tag == gimli::constants::DW_TAG_subroutine_type
+        || tag == gimli::constants::DW_TAG_unspecified_type
+}
@@ -0,0 +1,394 @@
+use gimli::write::{EndianVec, InitialLengthOffset, Sections, Writer};
+use gimli::{EndianSlice, Format, RunTimeEndian, SectionId};
+use std::collections::HashMap;
+
+type OutputSections = Sections<EndianVec<RunTimeEndian>>;
+
+/// a named entry that is listed in the name index sections
+struct IndexedName {
+    name: String,
+    die_offset: usize,
+    tag: gimli::DwTag,
+    is_external: bool,
+}
+
+/// the information about one output unit that is needed to build the index sections
+struct IndexedUnit {
+    offset: usize,
+    length: usize,
+    encoding: gimli::Encoding,
+    names: Vec<IndexedName>,
+    address_ranges: Vec<(u64, u64)>,
+}
+
+/// build the name and address index sections (.debug_aranges, .debug_pubnames, etc) from the obfuscated debug info.
+/// The result is a list of section names and their new content.
+/// If `with_debug_names` is set, the .debug_names section is built too; this appends its strings to .debug_str
+pub(crate) fn build_index_sections(
+    sections: &mut OutputSections,
+    endian: RunTimeEndian,
+    with_debug_names: bool,
+) -> Result<Vec<(&'static str, Vec<u8>)>, String> {
+    let units = read_output_units(sections, endian).map_err(|err| err.to_string())?;
+
+    let mut index_sections = vec![
+        (".debug_aranges", write_aranges(&units, endian)),
+        (
+            ".debug_pubnames",
+            write_pubnames(&units, endian, false, is_external_pubname),
+        ),
+        (
+            ".debug_pubtypes",
+            write_pubnames(&units, endian, false, is_pubtype),
+        ),
+        (
+            ".debug_gnu_pubnames",
+            write_pubnames(&units, endian, true, is_pubname),
+        ),
+        (
+            ".debug_gnu_pubtypes",
+            write_pubnames(&units, endian, true, is_pubtype),
+        ),
+    ];
+    if with_debug_names {
+        let debug_names = write_debug_names(&units, endian, &mut sections.debug_str);
+        index_sections.push((".debug_names", debug_names));
+    }
+
+    Ok(index_sections
+        .into_iter()
+        .map(|(name, data)| (name, data.into_vec()))
+        .collect())
+}
+
+// read back the units that were written, in order to get the final offsets of all entries
+fn read_output_units(
+    sections: &OutputSections,
+    endian: RunTimeEndian,
+) -> gimli::Result<Vec<IndexedUnit>> {
+    let dwarf = gimli::Dwarf::load(|id| -> gimli::Result<_> {
+        let data = sections.get(id).map_or(&[][..], |section| section.slice());
+        Ok(EndianSlice::new(data, endian))
+    })?;
+
+    let mut units = Vec::new();
+    let mut unit_iter = dwarf.debug_info.units();
+    while let Some(unit_header) = unit_iter.next()? {
+        let Some(offset) = unit_header.debug_info_offset() else {
+            continue;
+        };
+        let unit = dwarf.unit(unit_header)?;
+        let mut indexed_unit = IndexedUnit {
+            offset: offset.0,
+            length: unit_header.length_including_self(),
+            encoding: unit_header.encoding(),
+            names: Vec::new(),
+            address_ranges: Vec::new(),
+        };
+
+        // scopes[depth] is the qualified name prefix for entries at depth + 1, or None if the entries are not global
+        let mut scopes: Vec<Option<String>> = Vec::new();
+        let mut entries_cursor = unit.entries();
+        while let Some(entry) = entries_cursor.next_dfs()? {
+            let depth = entry.depth() as usize;
+            scopes.truncate(depth);
+            let name = entry
+                .attr_value(gimli::constants::DW_AT_name)
+                .and_then(|value| dwarf.attr_string(&unit, value).ok())
+                .and_then(|name| name.to_string().ok().map(str::to_string));
+            let parent_scope = if depth == 0 {
+                Some(String::new())
+            } else {
+                scopes.last().cloned().flatten()
+            };
+
+            if entry.tag() == gimli::constants::DW_TAG_subprogram
+                && let Some(range) = get_pc_range(entry)
+            {
+                indexed_unit.address_ranges.push(range);
+            }
+
+            let is_declaration = entry
+                .attr_value(gimli::constants::DW_AT_declaration)
+                .is_some_and(|value| value == gimli::AttributeValue::Flag(true));
+            if let (Some(prefix), Some(name)) = (&parent_scope, &name)
+                && depth > 0
+                && !is_declaration
+            {
+                let indexed_name = IndexedName {
+                    name: format!("{prefix}{name}"),
+                    die_offset: entry.offset().0,
+                    tag: entry.tag(),
+                    is_external: entry
+                        .attr_value(gimli::constants::DW_AT_external)
+                        .is_some_and(|value| value == gimli::AttributeValue::Flag(true)),
+                };
+                if is_pubname(&indexed_name)
+                    || is_pubtype(&indexed_name)
+                    || indexed_name.tag == gimli::constants::DW_TAG_namespace
+                {
+                    indexed_unit.names.push(indexed_name);
+                }
+            }
+
+            // only the unit, namespaces and types can contain further global names
+            let scope = match (parent_scope, entry.tag()) {
+                (Some(prefix), _) if depth == 0 => Some(prefix),
+                (Some(prefix), tag)
+                    if tag == gimli::constants::DW_TAG_namespace
+                        || tag == gimli::constants::DW_TAG_structure_type
+                        || tag == gimli::constants::DW_TAG_class_type
+                        || tag == gimli::constants::DW_TAG_union_type =>
+                {
+                    name.map(|name| format!("{prefix}{name}::"))
+                }
+                _ => None,
+            };
+            scopes.push(scope);
+        }
+        units.push(indexed_unit);
+    }
+
+    Ok(units)
+}
+
+// get the address range of a function as (start address, length)
+fn get_pc_range(
+    entry: &gimli::DebuggingInformationEntry<EndianSlice<RunTimeEndian>>,
+) -> Option<(u64, u64)> {
+    let gimli::AttributeValue::Addr(low_pc) = entry.attr_value(gimli::constants::DW_AT_low_pc)?
+    else {
+        return None;
+    };
+    let length = match entry.attr_value(gimli::constants::DW_AT_high_pc)? {
+        gimli::AttributeValue::Addr(high_pc) => high_pc.checked_sub(low_pc)?,
+        value => value.udata_value()?,
+    };
+    Some((low_pc, length))
+}
+
+fn is_pubname(name: &IndexedName) -> bool {
+    name.tag == gimli::constants::DW_TAG_variable || name.tag == gimli::constants::DW_TAG_subprogram
+}
+
+// .debug_pubnames only lists names that are visible outside of their unit
+fn is_external_pubname(name: &IndexedName) -> bool {
+    is_pubname(name) && name.is_external
+}
+
+fn is_pubtype(name: &IndexedName) -> bool {
+    name.tag == gimli::constants::DW_TAG_base_type
+        || name.tag == gimli::constants::DW_TAG_typedef
+        || name.tag == gimli::constants::DW_TAG_structure_type
+        || name.tag == gimli::constants::DW_TAG_class_type
+        || name.tag == gimli::constants::DW_TAG_union_type
+        || name.tag == gimli::constants::DW_TAG_enumeration_type
+}
+
+// .debug_aranges: one set of address ranges per unit
+fn write_aranges(units: &[IndexedUnit], endian: RunTimeEndian) -> EndianVec<RunTimeEndian> {
+    let mut writer = EndianVec::new(endian);
+    for unit in units {
+        let format = unit.encoding.format;
+        let address_size = unit.encoding.address_size;
+        let (start, length_offset) = start_set(&mut writer, format);
+        writer.write_u16(2).unwrap();
+        writer
+            .write_offset(unit.offset, SectionId::DebugInfo, format.word_size())
+            .unwrap();
+        writer.write_u8(address_size).unwrap();
+        // segment selector size
+        writer.write_u8(0).unwrap();
+        // the tuples are aligned to twice the address size
+        let tuple_size = 2 * usize::from(address_size);
+        while !(writer.len() - start).is_multiple_of(tuple_size) {
+            writer.write_u8(0).unwrap();
+        }
+        for (address, length) in &unit.address_ranges {
+            writer.write_udata(*address, address_size).unwrap();
+            writer.write_udata(*length, address_size).unwrap();
+        }
+        writer.write_udata(0, address_size).unwrap();
+        writer.write_udata(0, address_size).unwrap();
+        finish_set(&mut writer, start, length_offset, format);
+    }
+    writer
+}
+
+// .debug_pubnames / .debug_pubtypes, and the GNU variants, which add a flag byte to each name
+fn write_pubnames(
+    units: &[IndexedUnit],
+    endian: RunTimeEndian,
+    gnu_flags: bool,
+    filter: fn(&IndexedName) -> bool,
+) -> EndianVec<RunTimeEndian> {
+    let mut writer = EndianVec::new(endian);
+    for unit in units {
+        let format = unit.encoding.format;
+        let (start, length_offset) = start_set(&mut writer, format);
+        writer.write_u16(2).unwrap();
+        writer
+            .write_offset(unit.offset, SectionId::DebugInfo, format.word_size())
+            .unwrap();
+        writer
+            .write_udata(unit.length as u64, format.word_size())
+            .unwrap();
+        for name in unit.names.iter().filter(|name| filter(name)) {
+            writer
+                .write_udata(name.die_offset as u64, format.word_size())
+                .unwrap();
+            if gnu_flags {
+                writer.write_u8(gnu_index_flags(name)).unwrap();
+            }
+            writer.write(name.name.as_bytes()).unwrap();
+            writer.write_u8(0).unwrap();
+        }
+        writer.write_udata(0, format.word_size()).unwrap();
+        finish_set(&mut writer, start, length_offset, format);
+    }
+    writer
+}
+
+// the flag byte of the .debug_gnu_pubnames entries: symbol kind in bits 4-6, static in bit 7
+fn gnu_index_flags(name: &IndexedName) -> u8 {
+    let kind = if name.tag == gimli::constants::DW_TAG_variable {
+        2
+    } else if name.tag == gimli::constants::DW_TAG_subprogram {
+        3
+    } else {
+        1
+    };
+    let is_static = !name.is_external && kind != 1;
+    (u8::from(is_static) << 7) | (kind << 4)
+}
+
+// .debug_names: a single name index for all units, without the optional hash table
+fn write_debug_names(
+    units: &[IndexedUnit],
+    endian: RunTimeEndian,
+    debug_str: &mut EndianVec<RunTimeEndian>,
+) -> EndianVec<RunTimeEndian> {
+    let format = units
+        .first()
+        .map_or(Format::Dwarf32, |unit| unit.encoding.format);
+
+    // group the entries by name; each name is stored once in .debug_str
+    let mut names = Vec::<(&str, Vec<(usize, &IndexedName)>)>::new();
+    let mut name_positions = HashMap::<&str, usize>::new();
+    let mut abbrev_codes = Vec::<gimli::DwTag>::new();
+    for (unit_idx, unit) in units.iter().enumerate() {
+        for name in &unit.names {
+            let position = *name_positions.entry(&name.name).or_insert_with(|| {
+                names.push((&name.name, Vec::new()));
+                names.len() - 1
+            });
+            names[position].1.push((unit_idx, name));
+            if !abbrev_codes.contains(&name.tag) {
+                abbrev_codes.push(name.tag);
+            }
+        }
+    }
+
+    let mut abbrev_table = EndianVec::new(endian);
+    for (idx, tag) in abbrev_codes.iter().enumerate() {
+        abbrev_table.write_uleb128(idx as u64 + 1).unwrap();
+        abbrev_table.write_uleb128(u64::from(tag.0)).unwrap();
+        abbrev_table
+            .write_uleb128(u64::from(gimli::constants::DW_IDX_compile_unit.0))
+            .unwrap();
+        abbrev_table
+            .write_uleb128(u64::from(gimli::constants::DW_FORM_udata.0))
+            .unwrap();
+        abbrev_table
+            .write_uleb128(u64::from(gimli::constants::DW_IDX_die_offset.0))
+            .unwrap();
+        abbrev_table
+            .write_uleb128(u64::from(gimli::constants::DW_FORM_ref4.0))
+            .unwrap();
+        abbrev_table.write_uleb128(0).unwrap();
+        abbrev_table.write_uleb128(0).unwrap();
+    }
+    abbrev_table.write_uleb128(0).unwrap();
+
+    let mut string_offsets = Vec::with_capacity(names.len());
+    let mut entry_offsets = Vec::with_capacity(names.len());
+    let mut entry_pool = EndianVec::new(endian);
+    for (name, entries) in &names {
+        string_offsets.push(debug_str.len());
+        debug_str.write(name.as_bytes()).unwrap();
+        debug_str.write_u8(0).unwrap();
+
+        entry_offsets.push(entry_pool.len());
+        for (unit_idx, entry) in entries {
+            let code = abbrev_codes
+                .iter()
+                .position(|tag| *tag == entry.tag)
+                .unwrap()
+                + 1;
+            entry_pool.write_uleb128(code as u64).unwrap();
+            entry_pool.write_uleb128(*unit_idx as u64).unwrap();
+            entry_pool.write_u32(entry.die_offset as u32).unwrap();
+        }
+        entry_pool.write_uleb128(0).unwrap();
+    }
+
+    let mut writer = EndianVec::new(endian);
+    let (start, length_offset) = start_set(&mut writer, format);
+    writer.write_u16(5).unwrap();
+    // padding
+    writer.write_u16(0).unwrap();
+    writer.write_u32(units.len() as u32).unwrap();
+    // local and foreign type units
+    writer.write_u32(0).unwrap();
+    writer.write_u32(0).unwrap();
+    // bucket count; without buckets there is no hash table
+    writer.write_u32(0).unwrap();
+    writer.write_u32(names.len() as u32).unwrap();
+    writer.write_u32(abbrev_table.len() as u32).unwrap();
+    // augmentation string size
+    writer.write_u32(0).unwrap();
+    for unit in units {
+        writer
+            .write_offset(unit.offset, SectionId::DebugInfo, format.word_size())
+            .unwrap();
+    }
+    for offset in string_offsets {
+        writer
+            .write_offset(offset, SectionId::DebugStr, format.word_size())
+            .unwrap();
+    }
+    for offset in entry_offsets {
+        writer
+            .write_udata(offset as u64, format.word_size())
+            .unwrap();
+    }
+    writer.write(abbrev_table.slice()).unwrap();
+    writer.write(entry_pool.slice()).unwrap();
+    finish_set(&mut writer, start, length_offset, format);
+
+    writer
+}
+
+// start a new set (unit) in an index section by writing a placeholder for the length
+fn start_set(
+    writer: &mut EndianVec<RunTimeEndian>,
+    format: Format,
+) -> (usize, InitialLengthOffset) {
+    let start = writer.len();
+    let length_offset = writer.write_initial_length(format).unwrap();
+    (start, length_offset)
+}
+
+// update the length of the set, now that its full content has been written
+fn finish_set(
+    writer: &mut EndianVec<RunTimeEndian>,
+    start: usize,
+    length_offset: InitialLengthOffset,
+    format: Format,
+) {
+    let length_size = if format == Format::Dwarf64 { 12 } else { 4 };
+    let length = writer.len() - start - length_size;
+    writer
+        .write_initial_length_at(length_offset, length as u64, format)
+        .unwrap();
+}
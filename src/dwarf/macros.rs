@@ -0,0 +1,303 @@
+use super::{SliceType, mapped_name, typename};
+use crate::keeplist::KeepList;
+use crate::options::ScrubSource;
+use gimli::write::{EndianVec, Writer};
+use gimli::{Dwarf, Reader, RunTimeEndian, Section};
+use std::collections::{HashMap, VecDeque};
+
+// keywords that can occur in the body of a macro, in addition to the keywords of type names
+static MACRO_KEYWORDS: &[&str] = &[
+    "auto", "break", "case", "continue", "default", "defined", "do", "else", "extern", "for",
+    "goto", "if", "inline", "register", "restrict", "return", "static", "switch", "typedef",
+    "while",
+];
+
+/// the rewritten .debug_macro and .debug_macinfo sections
+pub(super) struct ObfuscatedMacros {
+    debug_macro: EndianVec<RunTimeEndian>,
+    debug_macinfo: EndianVec<RunTimeEndian>,
+    /// original offset of a macro unit in .debug_macro -> offset in the new section
+    pub(super) macro_offsets: HashMap<usize, usize>,
+    /// original offset of the macro info of a compile unit in .debug_macinfo -> offset in the new section
+    pub(super) macinfo_offsets: HashMap<usize, usize>,
+}
+
+impl ObfuscatedMacros {
+    /// the new section contents, for all sections that contain data
+    pub(super) fn into_sections(self) -> Vec<(&'static str, Vec<u8>)> {
+        [
+            (".debug_macro", self.debug_macro),
+            (".debug_macinfo", self.debug_macinfo),
+        ]
+        .into_iter()
+        .filter(|(_, data)| !data.slice().is_empty())
+        .map(|(name, data)| (name, data.into_vec()))
+        .collect()
+    }
+}
+
+/// Rewrite the macro information of all units. Macro names and the identifiers in the macro
+/// definitions are obfuscated with the same mapping as the names in the debug info, so that
+/// a macro which refers to a variable still refers to it after obfuscation.
+/// The strings are always stored directly in the macro entries, because the offsets in .debug_str change.
+/// Source file information is removed, since it refers to the line programs, which are not copied
+pub(super) fn obfuscate_macros(
+    input_dwarf: &Dwarf<SliceType>,
+    input_units: &[gimli::UnitHeader<SliceType>],
+    scrub_source: ScrubSource,
+    keep_list: &KeepList,
+    obfuscated_strings: &mut HashMap<String, String>,
+) -> ObfuscatedMacros {
+    let endian = input_dwarf.debug_info.reader().endian();
+    let mut macros = ObfuscatedMacros {
+        debug_macro: EndianVec::new(endian),
+        debug_macinfo: EndianVec::new(endian),
+        macro_offsets: HashMap::new(),
+        macinfo_offsets: HashMap::new(),
+    };
+    let mut writer = MacroWriter {
+        scrub_source,
+        keep_list,
+        obfuscated_strings,
+    };
+
+    for header in input_units {
+        let Ok(unit) = input_dwarf.unit(*header) else {
+            continue;
+        };
+        let unit_ref = unit.unit_ref(input_dwarf);
+        let mut entries = unit.entries();
+        let Ok(Some(root)) = entries.next_dfs() else {
+            continue;
+        };
+
+        // DWARF 5 uses DW_AT_macros, gcc uses DW_AT_GNU_macros for the same format in DWARF 4
+        let macro_offset = match (
+            root.attr_value(gimli::constants::DW_AT_macros),
+            root.attr_value(gimli::constants::DW_AT_GNU_macros),
+        ) {
+            (Some(gimli::AttributeValue::DebugMacroRef(offset)), _) => Some(offset.0),
+            (_, Some(gimli::AttributeValue::SecOffset(offset))) => Some(offset),
+            _ => None,
+        };
+        if let Some(offset) = macro_offset {
+            writer.write_macro_units(offset, unit_ref, &mut macros);
+        }
+
+        if let Some(gimli::AttributeValue::DebugMacinfoRef(offset)) =
+            root.attr_value(gimli::constants::DW_AT_macro_info)
+            && !macros.macinfo_offsets.contains_key(&offset.0)
+        {
+            let new_offset = macros.debug_macinfo.len();
+            let entries = input_dwarf.macinfo(offset).ok();
+            writer.write_entries(&mut macros.debug_macinfo, entries, unit_ref, &mut vec![]);
+            macros.macinfo_offsets.insert(offset.0, new_offset);
+        }
+    }
+
+    macros
+}
+
+struct MacroWriter<'a> {
+    scrub_source: ScrubSource,
+    keep_list: &'a KeepList,
+    obfuscated_strings: &'a mut HashMap<String, String>,
+}
+
+impl MacroWriter<'_> {
+    // write the macro unit at the given offset and all units that it imports, unless they were written before
+    fn write_macro_units(
+        &mut self,
+        offset: usize,
+        unit_ref: gimli::UnitRef<SliceType>,
+        macros: &mut ObfuscatedMacros,
+    ) {
+        let mut queue = VecDeque::from([offset]);
+        // position of each import in the new section, and the original offset of the imported unit
+        let mut imports = vec![];
+        while let Some(offset) = queue.pop_front() {
+            if macros.macro_offsets.contains_key(&offset) {
+                continue;
+            }
+            let output = &mut macros.debug_macro;
+            macros.macro_offsets.insert(offset, output.len());
+
+            // the header: the version is copied, the flags select 32-bit offsets and no line program
+            let version = read_macro_version(unit_ref, offset).unwrap_or(5);
+            output.write_u16(version).unwrap();
+            output.write_u8(0).unwrap();
+
+            let entries = unit_ref.macros(gimli::DebugMacroOffset(offset)).ok();
+            let first_import = imports.len();
+            self.write_entries(output, entries, unit_ref, &mut imports);
+            queue.extend(imports[first_import..].iter().map(|(_, target)| *target));
+        }
+
+        for (position, target) in imports {
+            let new_target = macros.macro_offsets.get(&target).copied().unwrap_or(0);
+            macros
+                .debug_macro
+                .write_u32_at(position, new_target as u32)
+                .unwrap();
+        }
+    }
+
+    // write the obfuscated entries of one macro unit, followed by the terminating 0
+    fn write_entries<'data>(
+        &mut self,
+        output: &mut EndianVec<RunTimeEndian>,
+        entries: Option<gimli::MacroIter<SliceType<'data>>>,
+        unit_ref: gimli::UnitRef<SliceType<'data>>,
+        imports: &mut Vec<(usize, usize)>,
+    ) {
+        // an entry that can't be read ends the list; everything before it is kept
+        for entry in entries.into_iter().flatten().map_while(Result::ok) {
+            match entry {
+                gimli::MacroEntry::Define { line, text } => {
+                    let Some(text) = macro_string(&text, unit_ref) else {
+                        continue;
+                    };
+                    output
+                        .write_u8(gimli::constants::DW_MACRO_define.0)
+                        .unwrap();
+                    output.write_uleb128(self.line(line)).unwrap();
+                    self.write_text(output, &text);
+                }
+                gimli::MacroEntry::Undef { line, name } => {
+                    let Some(name) = macro_string(&name, unit_ref) else {
+                        continue;
+                    };
+                    output.write_u8(gimli::constants::DW_MACRO_undef.0).unwrap();
+                    output.write_uleb128(self.line(line)).unwrap();
+                    self.write_text(output, &name);
+                }
+                gimli::MacroEntry::Import { offset } => {
+                    output
+                        .write_u8(gimli::constants::DW_MACRO_import.0)
+                        .unwrap();
+                    imports.push((output.len(), offset.0));
+                    output.write_u32(0).unwrap();
+                }
+                // The file numbers of DW_MACRO_start_file refer to the file names in the line program,
+                // which is not copied. There is no supplementary file in the output, and the meaning
+                // of vendor extensions is unknown
+                gimli::MacroEntry::StartFile { .. }
+                | gimli::MacroEntry::EndFile
+                | gimli::MacroEntry::ImportSup { .. }
+                | gimli::MacroEntry::VendorExt { .. } => {}
+            }
+        }
+        output.write_u8(0).unwrap();
+    }
+
+    // the line number of a macro entry. Line 0 is used for predefined macros and command line options
+    fn line(&self, line: u64) -> u64 {
+        if self.scrub_source == ScrubSource::Keep || line == 0 {
+            line
+        } else {
+            1
+        }
+    }
+
+    fn write_text(&mut self, output: &mut EndianVec<RunTimeEndian>, text: &str) {
+        let obfuscated = obfuscate_macro_text(text, self.keep_list, self.obfuscated_strings);
+        output.write(obfuscated.as_bytes()).unwrap();
+        output.write_u8(0).unwrap();
+    }
+}
+
+// read the version from the header of a macro unit
+fn read_macro_version(unit_ref: gimli::UnitRef<SliceType>, offset: usize) -> Option<u16> {
+    let mut reader = *unit_ref.dwarf.debug_macro.reader();
+    reader.skip(offset).ok()?;
+    reader.read_u16().ok()
+}
+
+fn macro_string<'data>(
+    string: &gimli::MacroString<SliceType<'data>>,
+    unit_ref: gimli::UnitRef<SliceType<'data>>,
+) -> Option<String> {
+    let text = string.string(unit_ref).ok()?;
+    Some(text.to_string_lossy().into_owned())
+}
+
+/// Obfuscate the text of a macro definition, e.g. "CAL_SIZE(x) (sizeof(calParam) * x)".
+/// Identifiers are obfuscated through the shared mapping, with the exception of keywords,
+/// reserved names like __GNUC__ or _Bool, and the names on the keep list, e.g. NULL.
+/// Numbers and punctuation are kept, and the letters inside of string and character literals are replaced
+fn obfuscate_macro_text(
+    text: &str,
+    keep_list: &KeepList,
+    obfuscated_strings: &mut HashMap<String, String>,
+) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut chars = text.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        if c.is_ascii_alphabetic() || c == '_' {
+            let mut end = start + 1;
+            while let Some((pos, _)) =
+                chars.next_if(|(_, c)| c.is_ascii_alphanumeric() || *c == '_')
+            {
+                end = pos + 1;
+            }
+            let identifier = &text[start..end];
+            if is_reserved_identifier(identifier)
+                || typename::KEYWORDS.contains(&identifier)
+                || MACRO_KEYWORDS.contains(&identifier)
+                || keep_list.contains(identifier)
+            {
+                output.push_str(identifier);
+            } else {
+                output.push_str(&mapped_name(identifier, keep_list, obfuscated_strings));
+            }
+        } else if c.is_ascii_digit() {
+            // a preprocessing number, e.g. 0x1Fu or 1.5e-3f
+            output.push(c);
+            let mut prev = c;
+            while let Some((_, c)) = chars.next_if(|(_, c)| {
+                c.is_ascii_alphanumeric()
+                    || *c == '.'
+                    || *c == '_'
+                    || ((*c == '+' || *c == '-') && matches!(prev, 'e' | 'E' | 'p' | 'P'))
+            }) {
+                output.push(c);
+                prev = c;
+            }
+        } else if c == '"' || c == '\'' {
+            // string and character literals might contain anything, e.g. paths or version strings
+            output.push(c);
+            let mut escaped = false;
+            for (_, lit_char) in chars.by_ref() {
+                if escaped {
+                    output.push(lit_char);
+                    escaped = false;
+                } else if lit_char == '\\' {
+                    output.push(lit_char);
+                    escaped = true;
+                } else if lit_char == c {
+                    output.push(lit_char);
+                    break;
+                } else if lit_char.is_ascii_alphabetic() {
+                    output.push(random_letter(lit_char.is_ascii_uppercase()));
+                } else {
+                    output.push(lit_char);
+                }
+            }
+        } else {
+            output.push(c);
+        }
+    }
+    output
+}
+
+// names starting with two underscores or an underscore and an uppercase letter are reserved for
+// the compiler and the standard library. gcc and clang predefine hundreds of macros with such names
+fn is_reserved_identifier(identifier: &str) -> bool {
+    let bytes = identifier.as_bytes();
+    bytes.len() > 1 && bytes[0] == b'_' && (bytes[1] == b'_' || bytes[1].is_ascii_uppercase())
+}
+
+fn random_letter(uppercase: bool) -> char {
+    let base = if uppercase { b'A' } else { b'a' };
+    (base + rand::random_range(0..26)) as char
+}
@@ -0,0 +1,278 @@
+use super::{mapped_name, typename};
+use crate::keeplist::KeepList;
+use cpp_demangle::{DemangleNodeType, DemangleOptions, DemangleWrite, Symbol};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// collects the text of all top-level <unqualified-name> nodes while a symbol is demangled
+#[derive(Default)]
+struct NameCollector {
+    node_stack: Vec<DemangleNodeType>,
+    current: String,
+    names: Vec<String>,
+}
+
+impl DemangleWrite for NameCollector {
+    fn push_demangle_node(&mut self, node_type: DemangleNodeType) {
+        if node_type == DemangleNodeType::UnqualifiedName && !self.in_unqualified_name() {
+            self.current.clear();
+        }
+        self.node_stack.push(node_type);
+    }
+
+    fn write_string(&mut self, s: &str) -> fmt::Result {
+        if self.in_unqualified_name() {
+            self.current.push_str(s);
+        }
+        Ok(())
+    }
+
+    fn pop_demangle_node(&mut self) {
+        if self.node_stack.pop() == Some(DemangleNodeType::UnqualifiedName)
+            && !self.in_unqualified_name()
+        {
+            self.names.push(std::mem::take(&mut self.current));
+        }
+    }
+}
+
+impl NameCollector {
+    fn in_unqualified_name(&self) -> bool {
+        self.node_stack.contains(&DemangleNodeType::UnqualifiedName)
+    }
+}
+
+/// Obfuscate an Itanium C++ mangled name (e.g. _ZN6engine5calibE), so that it demangles to the
+/// obfuscated names of its components. Each <source-name> (a length followed by an identifier) is
+/// replaced by the obfuscated identifier, using the same mapping as the DW_AT_name attributes.
+/// If keep_std is set, the names of standard library entities are not changed.
+/// Returns None if the name is not a mangled name, or if it could not be processed.
+pub(super) fn obfuscate_mangled_name(
+    mangled: &str,
+    keep_std: bool,
+    keep_list: &KeepList,
+    obfuscated_strings: &mut HashMap<String, String>,
+) -> Option<String> {
+    if !mangled.starts_with("_Z") {
+        return None;
+    }
+    let original_names = demangled_names(mangled)?;
+    let demangled = if keep_std {
+        Symbol::new(mangled).ok()?.demangle().ok()?
+    } else {
+        String::new()
+    };
+    let std_identifiers = typename::std_identifiers(&demangled);
+    // only plain identifiers are encoded as <source-name>; operators, anonymous namespaces,
+    // abi tags etc. are written differently in the demangled output and are never matched
+    let identifiers: HashSet<&str> = original_names
+        .iter()
+        .map(String::as_str)
+        .filter(|name| {
+            !name.is_empty()
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+                && !std_identifiers.contains(name)
+        })
+        .collect();
+
+    // new names are only added to the shared mapping if the result passes the check below
+    let mut new_strings = HashMap::<String, String>::new();
+    let bytes = mangled.as_bytes();
+    let mut output = String::with_capacity(mangled.len());
+    let mut pos = 0;
+    while pos < bytes.len() {
+        if !bytes[pos].is_ascii_digit() {
+            output.push(bytes[pos] as char);
+            pos += 1;
+            continue;
+        }
+        // a run of digits might be the length of a <source-name>
+        let digits_end = bytes[pos..]
+            .iter()
+            .position(|c| !c.is_ascii_digit())
+            .map_or(bytes.len(), |len| pos + len);
+        // in a malformed name, the number could be too large for a length
+        let name_end = mangled[pos..digits_end]
+            .parse::<usize>()
+            .ok()
+            .and_then(|length| digits_end.checked_add(length));
+        if let Some(name_end) = name_end
+            && let Some(identifier) = mangled.get(digits_end..name_end)
+            && identifiers.contains(identifier)
+        {
+            let obfuscated = match obfuscated_strings.get(identifier) {
+                Some(obfuscated) => obfuscated.clone(),
+                None => mapped_name(identifier, keep_list, &mut new_strings),
+            };
+            output.push_str(&obfuscated.len().to_string());
+            output.push_str(&obfuscated);
+            pos = name_end;
+        } else {
+            output.push_str(&mangled[pos..digits_end]);
+            pos = digits_end;
+        }
+    }
+
+    // The replacement works on the text of the mangled name, so a number that happens to be followed
+    // by an identifier could be misinterpreted. Check that the result has exactly the expected structure.
+    let expected_names: Vec<String> = original_names
+        .iter()
+        .map(|name| {
+            // destructors are not encoded as a <source-name>, but their demangled name contains the class name
+            let (prefix, plain_name) = match name.strip_prefix('~') {
+                Some(class_name) => ("~", class_name),
+                None => ("", name.as_str()),
+            };
+            let obfuscated = obfuscated_strings
+                .get(plain_name)
+                .or_else(|| new_strings.get(plain_name));
+            match obfuscated {
+                Some(obfuscated) if identifiers.contains(plain_name) => {
+                    format!("{prefix}{obfuscated}")
+                }
+                _ => name.clone(),
+            }
+        })
+        .collect();
+    let new_names = demangled_names(&output)?;
+    if new_names.len() == expected_names.len()
+        && new_names
+            .iter()
+            .zip(&expected_names)
+            .all(|(new_name, expected)| new_name == expected)
+    {
+        obfuscated_strings.extend(new_strings);
+        Some(output)
+    } else {
+        None
+    }
+}
+
+// demangle a symbol and return the names of all of its components
+fn demangled_names(mangled: &str) -> Option<Vec<String>> {
+    let symbol = Symbol::new(mangled).ok()?;
+    let mut collector = NameCollector::default();
+    symbol
+        .structured_demangle(&mut collector, &DemangleOptions::default())
+        .ok()?;
+    Some(collector.names)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn obfuscate(mangled: &str, keep_std: bool) -> (Option<String>, HashMap<String, String>) {
+        let mut obfuscated_strings = HashMap::new();
+        let result = obfuscate_mangled_name(
+            mangled,
+            keep_std,
+            &KeepList::new(true),
+            &mut obfuscated_strings,
+        );
+        (result, obfuscated_strings)
+    }
+
+    // the names that a mangled name demangles to, after the original names were mapped
+    fn mapped_names(names: &[&str], obfuscated_strings: &HashMap<String, String>) -> Vec<String> {
+        names
+            .iter()
+            .map(|name| match name.strip_prefix('~') {
+                Some(class_name) => format!("~{}", obfuscated_strings[class_name]),
+                None => obfuscated_strings[*name].clone(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn nested_name() {
+        let (result, obfuscated_strings) = obfuscate("_ZN6engine5calibE", false);
+        let result = result.unwrap();
+        assert_eq!(obfuscated_strings.len(), 2);
+        assert_eq!(
+            demangled_names(&result).unwrap(),
+            mapped_names(&["engine", "calib"], &obfuscated_strings)
+        );
+        let engine = &obfuscated_strings["engine"];
+        let calib = &obfuscated_strings["calib"];
+        assert_eq!(result, format!("_ZN6{engine}5{calib}E"));
+    }
+
+    #[test]
+    fn template_with_numeric_literal() {
+        // Filter<float, 8>::apply(): the 8 is not the length of a name
+        let (result, obfuscated_strings) = obfuscate("_ZN6FilterIfLi8EE5applyEv", false);
+        let result = result.unwrap();
+        let filter = &obfuscated_strings["Filter"];
+        let apply = &obfuscated_strings["apply"];
+        assert_eq!(result, format!("_ZN6{filter}IfLi8EE5{apply}Ev"));
+    }
+
+    #[test]
+    fn oversized_numeric_literal() {
+        // the value is usize::MAX, which can't be added to the position in the name
+        let (result, obfuscated_strings) = obfuscate("_Z5applyILy18446744073709551615EEvv", false);
+        let apply = &obfuscated_strings["apply"];
+        assert_eq!(
+            result.unwrap(),
+            format!("_Z5{apply}ILy18446744073709551615EEvv")
+        );
+        // a value that doesn't even fit into usize
+        let (result, obfuscated_strings) =
+            obfuscate("_Z5applyILi99999999999999999999999EEvv", false);
+        let apply = &obfuscated_strings["apply"];
+        assert_eq!(
+            result.unwrap(),
+            format!("_Z5{apply}ILi99999999999999999999999EEvv")
+        );
+    }
+
+    #[test]
+    fn destructor() {
+        // engine::~engine(): the destructor name is not a <source-name>
+        let (result, obfuscated_strings) = obfuscate("_ZN6engineD2Ev", false);
+        let result = result.unwrap();
+        assert_eq!(obfuscated_strings.len(), 1);
+        assert_eq!(
+            demangled_names(&result).unwrap(),
+            mapped_names(&["engine", "~engine"], &obfuscated_strings)
+        );
+    }
+
+    #[test]
+    fn keep_std() {
+        // apply(std::vector<int, std::allocator<int>>&)
+        let (result, obfuscated_strings) = obfuscate("_Z5applyRSt6vectorIiSaIiEE", true);
+        let apply = &obfuscated_strings["apply"];
+        assert_eq!(result.unwrap(), format!("_Z5{apply}RSt6vectorIiSaIiEE"));
+        assert!(!obfuscated_strings.contains_key("vector"));
+
+        let (result, obfuscated_strings) = obfuscate("_Z5applyRSt6vectorIiSaIiEE", false);
+        let apply = &obfuscated_strings["apply"];
+        let vector = &obfuscated_strings["vector"];
+        assert_eq!(result.unwrap(), format!("_Z5{apply}RSt6{vector}IiSaIiEE"));
+    }
+
+    #[test]
+    fn rejected_name() {
+        // f<3, E3E>(): the literal 3 is followed by "E3E", which looks like the <source-name> of the class.
+        // The result does not demangle to the expected names, so nothing is added to the mapping
+        let (result, obfuscated_strings) = obfuscate("_Z1fILi3E3E3EEvv", false);
+        assert_eq!(result, None);
+        assert!(obfuscated_strings.is_empty());
+    }
+
+    #[test]
+    fn existing_mapping() {
+        let mut obfuscated_strings = HashMap::from([("calib".to_string(), "xyzzy".to_string())]);
+        let result = obfuscate_mangled_name(
+            "_ZN6engine5calibE",
+            false,
+            &KeepList::new(true),
+            &mut obfuscated_strings,
+        );
+        let engine = &obfuscated_strings["engine"];
+        assert_eq!(result.unwrap(), format!("_ZN6{engine}5xyzzyE"));
+        assert_eq!(obfuscate("calib", false), (None, HashMap::new()));
+    }
+}
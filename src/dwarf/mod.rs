@@ -0,0 +1,1136 @@
+use crate::a2l::debuginfo::simple_unit_name;
+use crate::keeplist::KeepList;
+use crate::layout::AddressLayout;
+use crate::options::{ConstValues, LinePrograms, Options, ScrubSource};
+use crate::report::Report;
+use gimli::{
+    Dwarf, EndianSlice, RunTimeEndian,
+    write::{UnitEntryId, UnitId},
+};
+use rand::rngs::StdRng;
+use std::{
+    collections::{HashMap, HashSet},
+    vec,
+};
+
+mod dedup;
+mod index;
+mod macros;
+mod mangling;
+mod order;
+mod policy;
+mod scrub;
+mod typename;
+mod version;
+
+pub(crate) use index::build_index_sections;
+use policy::AttributePolicy;
+
+type SliceType<'a> = EndianSlice<'a, RunTimeEndian>;
+
+/// the obfuscated debug info, the sections that gimli can't write (name and content),
+/// and the mapping from original to obfuscated names
+type ObfuscatedDwarf = (
+    gimli::write::Dwarf,
+    Vec<(&'static str, Vec<u8>)>,
+    HashMap<String, String>,
+);
+
+pub(crate) fn obfuscate_dwarf(
+    input_dwarf: Dwarf<EndianSlice<RunTimeEndian>>,
+    retained_variables: Option<&HashSet<usize>>,
+    address_layout: &AddressLayout,
+    options: &Options,
+    rng: &mut StdRng,
+    report: &mut Report,
+) -> Result<Option<ObfuscatedDwarf>, String> {
+    let mut output_dwarf = gimli::write::Dwarf::new();
+
+    let mut debuginfo_offsets = HashMap::<usize, (UnitId, UnitEntryId)>::new();
+
+    // mapping table from original strings to obfuscated strings
+    // this ensures that the same string is obfuscated to the same value
+    let mut obfuscated_strings = HashMap::<String, String>::new();
+
+    // in prune mode only the referenced variables and everything they depend on is copied to the output
+    let retained_entries =
+        retained_variables.map(|variables| collect_retained_entries(&input_dwarf, variables));
+    // identical types from different units only need to be emitted once; this is always done when
+    // all units are merged, otherwise references to the remaining copy cross unit boundaries
+    let duplicate_types = if options.merge_units || options.dedup_types {
+        dedup::find_duplicate_types(&input_dwarf, retained_entries.as_ref(), report)
+    } else {
+        HashMap::new()
+    };
+    let selection = EntrySelection {
+        retained: retained_entries,
+        duplicates: duplicate_types,
+    };
+    let mut merged_unit_id = None;
+    // variables in different units that would have the same name in the merged unit
+    let mut renamed_variables = HashMap::<usize, String>::new();
+    let mut merged_variable_names = HashMap::<String, gimli::UnitSectionOffset>::new();
+
+    // handle the dwarf info in two passes:
+    // - first pass: create all units amd all debug info entries in the units, while building
+    //   a mapping table for all input unit offsets and debuginfo offsets to the newly created unit entry ids
+    // - second pass: add all attributes to the entries in the output unit, using the mapping table from the first pass
+    //   While handling the attributes, we also obfuscate the names of the variables and functions
+
+    let mut input_units = vec![];
+    let mut unit_iter = input_dwarf.debug_info.units();
+    while let Ok(Some(unit)) = unit_iter.next() {
+        input_units.push(unit);
+    }
+    let mut per_unit_offsets = vec![None; input_units.len()];
+    let mut pending_entries = HashMap::<UnitId, Vec<order::PendingEntry>>::new();
+    // the names of the input units that went into each output unit
+    let mut original_unit_names = HashMap::<UnitId, Vec<String>>::new();
+    // the number of files in the line program of each output unit
+    let mut line_file_counts = HashMap::<UnitId, usize>::new();
+
+    // the output units are created in the requested order; the input units are always read in their original order
+    let mut unit_order: Vec<usize> = (0..input_units.len()).collect();
+    order::reorder(&mut unit_order, options.order, rng, |idx| {
+        let unit_name = input_dwarf
+            .unit(input_units[*idx])
+            .ok()
+            .and_then(|unit| get_unit_name(&unit));
+        order::obfuscated_sort_key(
+            unit_name.as_deref(),
+            (options.keep_std, false),
+            &options.keep_list,
+            &mut obfuscated_strings,
+        )
+    });
+
+    // pass 1
+    for unit_idx in unit_order {
+        let unit = &input_units[unit_idx];
+        let abbreviations = unit.abbreviations(&input_dwarf.debug_abbrev).unwrap();
+        // The root of the tree inside of a unit is always a DW_TAG_compile_unit or DW_TAG_partial_unit.
+        let mut entries_cursor = unit.entries(&abbreviations);
+        if let Ok(Some(entry)) = entries_cursor.next_dfs() {
+            if entry.tag() == gimli::constants::DW_TAG_compile_unit
+                || entry.tag() == gimli::constants::DW_TAG_partial_unit
+            {
+                let root_offset = entry.offset().to_debug_info_offset(unit).unwrap().0;
+                if selection
+                    .retained
+                    .as_ref()
+                    .is_some_and(|retained| !retained.contains(&root_offset))
+                {
+                    // nothing in this unit is needed
+                    continue;
+                }
+
+                let is_merged = merged_unit_id.is_some();
+                let unit_id = if let Some(unit_id) = merged_unit_id {
+                    unit_id
+                } else {
+                    let encoding = version::output_encoding(unit.encoding(), options);
+                    let output_unit =
+                        gimli::write::Unit::new(encoding, gimli::write::LineProgram::none());
+                    let unit_id = output_dwarf.units.add(output_unit);
+                    if options.merge_units {
+                        merged_unit_id = Some(unit_id);
+                    }
+                    unit_id
+                };
+                let output_unit = output_dwarf.units.get_mut(unit_id);
+
+                let mut unit_offsets = create_unit_structure(
+                    &input_dwarf,
+                    (unit_id, unit),
+                    &abbreviations,
+                    output_unit,
+                    &mut debuginfo_offsets,
+                    &selection,
+                    pending_entries.entry(unit_id).or_default(),
+                );
+                if is_merged {
+                    // only the first unit provides the attributes of the merged unit
+                    unit_offsets.remove(&entry.offset().0);
+                }
+                if options.merge_units {
+                    find_conflicting_variables(
+                        &input_dwarf,
+                        unit,
+                        &unit_offsets,
+                        &mut merged_variable_names,
+                        &mut renamed_variables,
+                    );
+                }
+
+                if let Ok(input_unit) = input_dwarf.unit(*unit) {
+                    if let Some(unit_name) = get_unit_name(&input_unit) {
+                        original_unit_names
+                            .entry(unit_id)
+                            .or_default()
+                            .push(unit_name);
+                    }
+                    let file_count = input_unit
+                        .line_program
+                        .as_ref()
+                        .map_or(0, |program| program.header().file_names().len());
+                    let max_count = line_file_counts.entry(unit_id).or_default();
+                    *max_count = (*max_count).max(file_count);
+                }
+
+                per_unit_offsets[unit_idx] = Some((unit_id, unit_offsets));
+            } else {
+                // error
+                panic!("impossible: first entry is not a compile unit or partial unit");
+            }
+        }
+    }
+
+    // references to a duplicate type are redirected to the emitted copy of the type.
+    // This can only be done now, because the emitted copy might be in a unit that was created later
+    for (duplicate, canonical) in &selection.duplicates {
+        if let Some(canonical_ids) = debuginfo_offsets.get(canonical).copied() {
+            debuginfo_offsets.insert(*duplicate, canonical_ids);
+        }
+    }
+
+    for (unit_id, pending) in pending_entries {
+        order::add_pending_entries(
+            output_dwarf.units.get_mut(unit_id),
+            pending,
+            options.order,
+            options.keep_std,
+            &options.keep_list,
+            rng,
+            &mut obfuscated_strings,
+        );
+    }
+
+    // the macro sections are written directly; the attributes of the units refer to the new offsets
+    let macros = macros::obfuscate_macros(
+        &input_dwarf,
+        &input_units,
+        options.scrub_source,
+        &options.keep_list,
+        &mut obfuscated_strings,
+    );
+
+    // pass 2
+    for (unit_idx, unit) in input_units.iter().enumerate() {
+        let abbreviations = unit.abbreviations(&input_dwarf.debug_abbrev).unwrap();
+        // The root of the tree inside of a unit is always a DW_TAG_compile_unit or DW_TAG_partial_unit.
+        let mut entries_cursor = unit.entries(&abbreviations);
+        if let Ok(Some(entry)) = entries_cursor.next_dfs() {
+            if entry.tag() == gimli::constants::DW_TAG_compile_unit
+                || entry.tag() == gimli::constants::DW_TAG_partial_unit
+            {
+                let Some((unit_id, unit_offsets)) = &per_unit_offsets[unit_idx] else {
+                    // the unit was pruned in pass 1
+                    continue;
+                };
+                let output_unit = output_dwarf.units.get_mut(*unit_id);
+                let context = AttributeContext {
+                    input_dwarf: &input_dwarf,
+                    input_unit: unit,
+                    unit_id: *unit_id,
+                    scrub_source: options.scrub_source,
+                    line_programs: options.line_programs,
+                    const_values: options.const_values,
+                    keep_std: options.keep_std,
+                    keep_list: &options.keep_list,
+                    macro_offsets: &macros.macro_offsets,
+                    macinfo_offsets: &macros.macinfo_offsets,
+                    unit_offsets,
+                    debuginfo_offsets: &debuginfo_offsets,
+                    address_layout,
+                    str_offsets_base: input_dwarf
+                        .unit(*unit)
+                        .map_or(gimli::DebugStrOffsetsBase(0), |unit| unit.str_offsets_base),
+                };
+                obfuscate_unit(
+                    &context,
+                    &abbreviations,
+                    output_unit,
+                    &mut obfuscated_strings,
+                    report,
+                );
+                if options.merge_units {
+                    rename_conflicting_variables(
+                        &input_dwarf,
+                        unit,
+                        unit_offsets,
+                        output_unit,
+                        &renamed_variables,
+                        &mut obfuscated_strings,
+                    );
+                }
+            } else {
+                // error
+                panic!("impossible: first entry is not a compile unit or partial unit");
+            }
+        }
+    }
+
+    if let Some(unit_id) = merged_unit_id {
+        // the address ranges of the first unit don't describe the merged unit
+        let output_unit = output_dwarf.units.get_mut(unit_id);
+        let root = output_unit.get_mut(output_unit.root());
+        root.delete(gimli::constants::DW_AT_low_pc);
+        root.delete(gimli::constants::DW_AT_high_pc);
+        root.delete(gimli::constants::DW_AT_ranges);
+    }
+
+    rename_units(
+        &mut output_dwarf,
+        &original_unit_names,
+        options.scrub_source,
+        &mut obfuscated_strings,
+    );
+
+    if options.line_programs == LinePrograms::Synthetic {
+        for (unit_id, file_count) in line_file_counts {
+            let output_unit = output_dwarf.units.get_mut(unit_id);
+            output_unit.line_program = scrub::synthetic_line_program(output_unit, file_count);
+        }
+    }
+
+    Ok(Some((
+        output_dwarf,
+        macros.into_sections(),
+        obfuscated_strings,
+    )))
+}
+
+/// decisions about which input entries are copied to the output, made before the output units are created
+struct EntrySelection {
+    /// entries that are retained in prune mode; None if all entries are retained
+    retained: Option<HashSet<usize>>,
+    /// duplicate type entries -> the equivalent entry that is emitted instead
+    duplicates: HashMap<usize, usize>,
+}
+
+/// lookup tables that are needed to convert the attributes of the entries in one unit
+struct AttributeContext<'a, 'data> {
+    input_dwarf: &'a Dwarf<SliceType<'data>>,
+    input_unit: &'a gimli::UnitHeader<SliceType<'data>>,
+    unit_id: UnitId,
+    scrub_source: ScrubSource,
+    line_programs: LinePrograms,
+    const_values: ConstValues,
+    keep_std: bool,
+    keep_list: &'a KeepList,
+    macro_offsets: &'a HashMap<usize, usize>,
+    macinfo_offsets: &'a HashMap<usize, usize>,
+    unit_offsets: &'a HashMap<usize, UnitEntryId>,
+    debuginfo_offsets: &'a HashMap<usize, (UnitId, UnitEntryId)>,
+    address_layout: &'a AddressLayout,
+    str_offsets_base: gimli::DebugStrOffsetsBase<usize>,
+}
+
+/// find all debug info entries that are needed to describe the given variables:
+/// the variables themselves, all entries they reference (types, declarations) and the
+/// entries that contain them. Functions and lexical blocks that contain a static variable are
+/// retained as containers without their other children, so that the variable keeps its function context.
+fn collect_retained_entries(
+    input_dwarf: &Dwarf<EndianSlice<RunTimeEndian>>,
+    variables: &HashSet<usize>,
+) -> HashSet<usize> {
+    let entry_links = collect_entry_links(input_dwarf);
+
+    let mut retained = HashSet::<usize>::new();
+    let mut worklist: Vec<usize> = variables.iter().copied().collect();
+    while let Some(offset) = worklist.pop() {
+        if !retained.insert(offset) {
+            continue;
+        }
+        let Some(links) = entry_links.get(&offset) else {
+            continue;
+        };
+
+        // everything the entry refers to is needed, e.g. the type of a variable or the type of a struct member
+        worklist.extend(&links.references);
+
+        // the children of types are part of the type: struct members, enumerators, array dimensions, etc.
+        // Member functions are not needed to describe the data layout.
+        if !is_code_scope(links.tag)
+            && links.tag != gimli::constants::DW_TAG_compile_unit
+            && links.tag != gimli::constants::DW_TAG_partial_unit
+            && links.tag != gimli::constants::DW_TAG_namespace
+            && links.tag != gimli::constants::DW_TAG_variable
+        {
+            worklist.extend(links.children.iter().filter(|child| {
+                entry_links
+                    .get(*child)
+                    .is_some_and(|child_links| !is_code_scope(child_links.tag))
+            }));
+        }
+
+        // the containers of the entry (functions, lexical blocks, namespaces, classes and the unit itself) are needed too
+        let mut parent = links.parent;
+        while let Some(parent_offset) = parent {
+            let Some(parent_links) = entry_links.get(&parent_offset) else {
+                break;
+            };
+            worklist.push(parent_offset);
+            parent = parent_links.parent;
+        }
+    }
+
+    retained
+}
+
+/// the structural information about a debug info entry that is needed to decide if it should be retained
+struct EntryLinks {
+    tag: gimli::DwTag,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    references: Vec<usize>,
+}
+
+// build a map of debuginfo offset -> EntryLinks for all entries in all units
+fn collect_entry_links(
+    input_dwarf: &Dwarf<EndianSlice<RunTimeEndian>>,
+) -> HashMap<usize, EntryLinks> {
+    let mut entry_links = HashMap::<usize, EntryLinks>::new();
+
+    let mut unit_iter = input_dwarf.debug_info.units();
+    while let Ok(Some(unit)) = unit_iter.next() {
+        let abbreviations = unit.abbreviations(&input_dwarf.debug_abbrev).unwrap();
+        let mut parents = Vec::<usize>::new();
+        let mut entries_cursor = unit.entries(&abbreviations);
+        while let Ok(Some(entry)) = entries_cursor.next_dfs() {
+            let offset = entry.offset().to_debug_info_offset(&unit).unwrap().0;
+            parents.truncate(entry.depth() as usize);
+            let parent = parents.last().copied();
+            parents.push(offset);
+
+            let mut references = Vec::new();
+            for attr in entry.attrs() {
+                // DW_AT_sibling only serves to skip over the children of the entry
+                if attr.name() == gimli::constants::DW_AT_sibling {
+                    continue;
+                }
+                match attr.value() {
+                    gimli::AttributeValue::UnitRef(unit_offset) => {
+                        if let Some(debuginfo_offset) = unit_offset.to_debug_info_offset(&unit) {
+                            references.push(debuginfo_offset.0);
+                        }
+                    }
+                    gimli::AttributeValue::DebugInfoRef(debuginfo_offset) => {
+                        references.push(debuginfo_offset.0);
+                    }
+                    _ => {}
+                }
+            }
+
+            if let Some(parent_offset) = parent
+                && let Some(parent_links) = entry_links.get_mut(&parent_offset)
+            {
+                parent_links.children.push(offset);
+            }
+            entry_links.insert(
+                offset,
+                EntryLinks {
+                    tag: entry.tag(),
+                    parent,
+                    children: Vec::new(),
+                    references,
+                },
+            );
+        }
+    }
+
+    entry_links
+}
+
+// entries that describe code rather than data
+fn is_code_scope(tag: gimli::DwTag) -> bool {
+    tag == gimli::constants::DW_TAG_subprogram
+        || tag == gimli::constants::DW_TAG_lexical_block
+        || tag == gimli::constants::DW_TAG_inlined_subroutine
+        || tag == gimli::constants::DW_TAG_entry_point
+        || tag == gimli::constants::DW_TAG_try_block
+        || tag == gimli::constants::DW_TAG_catch_block
+        || tag == gimli::constants::DW_TAG_call_site
+        || tag == gimli::constants::DW_TAG_GNU_call_site
+}
+
+fn create_unit_structure(
+    input_dwarf: &Dwarf<EndianSlice<RunTimeEndian>>,
+    (unit_id, in_unit): (UnitId, &gimli::UnitHeader<EndianSlice<RunTimeEndian>>),
+    in_abbrevs: &gimli::Abbreviations,
+    output_unit: &mut gimli::write::Unit,
+    debuginfo_offsets: &mut HashMap<usize, (UnitId, UnitEntryId)>,
+    selection: &EntrySelection,
+    pending_entries: &mut Vec<order::PendingEntry>,
+) -> HashMap<usize, UnitEntryId> {
+    // parent_ids[depth] is the output entry that receives the children of the input entry at that depth
+    let mut parent_ids = vec![];
+    let mut unit_offsets = HashMap::<usize, UnitEntryId>::new();
+    // depth of the namespace std entry, while its children are processed
+    let mut std_depth = None;
+
+    let mut entries_cursor = in_unit.entries(in_abbrevs);
+    while let Ok(Some(entry)) = entries_cursor.next_dfs() {
+        let depth = entry.depth() as usize;
+        parent_ids.truncate(depth);
+        if std_depth.is_some_and(|std_depth| depth <= std_depth) {
+            std_depth = None;
+        }
+        if std_depth.is_none()
+            && entry.tag() == gimli::constants::DW_TAG_namespace
+            && get_entry_name(input_dwarf, entry).as_deref() == Some("std")
+        {
+            std_depth = Some(depth);
+        }
+        let debuginfo_offset = entry.offset().to_debug_info_offset(in_unit).unwrap().0;
+
+        let id = if depth == 0 {
+            // the unit entry corresponds to the root of the output unit
+            output_unit.root()
+        } else if selection.duplicates.contains_key(&debuginfo_offset) {
+            // an identical type is emitted instead; references are redirected once all units have been created
+            parent_ids.push(parent_ids[depth - 1]);
+            continue;
+        } else if selection
+            .retained
+            .as_ref()
+            .is_none_or(|retained| retained.contains(&debuginfo_offset))
+        {
+            // create a new entry in the output unit. It is only added to its parent once all
+            // siblings are known, so that the siblings can be reordered
+            let id = output_unit.reserve();
+            pending_entries.push(order::PendingEntry {
+                parent: parent_ids[depth - 1],
+                id,
+                tag: entry.tag(),
+                name: get_entry_name(input_dwarf, entry),
+                in_std: std_depth.is_some(),
+            });
+            id
+        } else {
+            // the entry is not retained; its children (if any are retained) move up to the nearest retained ancestor
+            parent_ids.push(parent_ids[depth - 1]);
+            continue;
+        };
+        // make the entry id available in the parent_ids list so that sub-entries can be added to it
+        parent_ids.push(id);
+
+        // unit_offset in the input corresponds to the entry id in the output unit
+        unit_offsets.insert(entry.offset().0, id);
+        debuginfo_offsets.insert(debuginfo_offset, (unit_id, id));
+    }
+
+    unit_offsets
+}
+
+// In a merged unit, static variables from different source files could end up with the same name.
+// Find these variables, so that they can be given distinct names
+fn find_conflicting_variables(
+    input_dwarf: &Dwarf<EndianSlice<RunTimeEndian>>,
+    in_unit: &gimli::UnitHeader<EndianSlice<RunTimeEndian>>,
+    unit_offsets: &HashMap<usize, UnitEntryId>,
+    merged_variable_names: &mut HashMap<String, gimli::UnitSectionOffset>,
+    renamed_variables: &mut HashMap<usize, String>,
+) {
+    let Ok(unit) = input_dwarf.unit(*in_unit) else {
+        return;
+    };
+    let Some(unit_name) = get_unit_name(&unit) else {
+        return;
+    };
+    let unit_start = in_unit.offset();
+
+    let mut entries_cursor = unit.entries();
+    while let Ok(Some(entry)) = entries_cursor.next_dfs() {
+        // only variables directly inside the unit that have a storage location can conflict.
+        // Declarations share the name of their definition.
+        if entry.depth() != 1
+            || entry.tag() != gimli::constants::DW_TAG_variable
+            || entry.attr(gimli::constants::DW_AT_location).is_none()
+            || !unit_offsets.contains_key(&entry.offset().0)
+        {
+            continue;
+        }
+        let Some(name) = entry
+            .attr(gimli::constants::DW_AT_name)
+            .and_then(|attr| input_dwarf.attr_string(&unit, attr.value()).ok())
+            .and_then(|name| name.to_string().ok().map(str::to_string))
+        else {
+            continue;
+        };
+        match merged_variable_names.get(&name) {
+            Some(first_unit) if *first_unit != unit_start => {
+                let offset = entry.offset().to_debug_info_offset(in_unit).unwrap().0;
+                renamed_variables.insert(offset, simple_unit_name(&unit_name));
+            }
+            Some(_) => {}
+            None => {
+                merged_variable_names.insert(name, unit_start);
+            }
+        }
+    }
+}
+
+// give distinct names to the variables found by find_conflicting_variables.
+// The new name is stored in the string mapping as "name{CompileUnit:unit_c}",
+// which matches the qualifier that Vector tools add to the symbol link
+fn rename_conflicting_variables(
+    input_dwarf: &Dwarf<EndianSlice<RunTimeEndian>>,
+    in_unit: &gimli::UnitHeader<EndianSlice<RunTimeEndian>>,
+    unit_offsets: &HashMap<usize, UnitEntryId>,
+    output_unit: &mut gimli::write::Unit,
+    renamed_variables: &HashMap<usize, String>,
+    obfuscated_strings: &mut HashMap<String, String>,
+) {
+    let Ok(unit) = input_dwarf.unit(*in_unit) else {
+        return;
+    };
+    let mut entries_cursor = unit.entries();
+    while let Ok(Some(entry)) = entries_cursor.next_dfs() {
+        let offset = entry.offset().to_debug_info_offset(in_unit).unwrap().0;
+        let (Some(unit_name), Some(id)) = (
+            renamed_variables.get(&offset),
+            unit_offsets.get(&entry.offset().0),
+        ) else {
+            continue;
+        };
+        let Some(name) = entry
+            .attr(gimli::constants::DW_AT_name)
+            .and_then(|attr| input_dwarf.attr_string(&unit, attr.value()).ok())
+            .and_then(|name| name.to_string().ok().map(str::to_string))
+        else {
+            continue;
+        };
+        let obfuscated_name = obfuscate_name(&name);
+        output_unit.get_mut(*id).set(
+            gimli::constants::DW_AT_name,
+            gimli::write::AttributeValue::String(obfuscated_name.as_bytes().to_vec()),
+        );
+        obfuscated_strings.insert(
+            format!("{name}{{CompileUnit:{unit_name}}}"),
+            obfuscated_name,
+        );
+    }
+}
+
+// Give the output units synthetic names when source information is scrubbed.
+// The a2l file refers to units in {CompileUnit:unit_c} qualifiers, so the string mapping
+// gets an entry "{CompileUnit:unit_c}" -> "new_unit_c" for each input unit
+fn rename_units(
+    output_dwarf: &mut gimli::write::Dwarf,
+    original_unit_names: &HashMap<UnitId, Vec<String>>,
+    scrub_source: ScrubSource,
+    obfuscated_strings: &mut HashMap<String, String>,
+) {
+    let mut synthetic_names = HashSet::<String>::new();
+    for (unit_id, unit_names) in original_unit_names {
+        let output_unit = output_dwarf.units.get_mut(*unit_id);
+        let root = output_unit.get_mut(output_unit.root());
+        if scrub_source != ScrubSource::Keep {
+            let language = match root.get(gimli::constants::DW_AT_language) {
+                Some(gimli::write::AttributeValue::Language(language)) => Some(*language),
+                _ => None,
+            };
+            let name = scrub::synthetic_unit_name(language, &mut synthetic_names);
+            root.set(
+                gimli::constants::DW_AT_name,
+                gimli::write::AttributeValue::String(name.into_bytes()),
+            );
+        }
+
+        if let Some(gimli::write::AttributeValue::String(new_name)) =
+            root.get(gimli::constants::DW_AT_name)
+        {
+            let new_simple_name = simple_unit_name(&String::from_utf8_lossy(new_name));
+            for unit_name in unit_names {
+                obfuscated_strings.insert(
+                    format!("{{CompileUnit:{}}}", simple_unit_name(unit_name)),
+                    new_simple_name.clone(),
+                );
+            }
+        }
+    }
+}
+
+// get the DW_AT_name of an entry, if it is stored in one of the forms that are obfuscated
+fn get_entry_name(
+    input_dwarf: &Dwarf<EndianSlice<RunTimeEndian>>,
+    entry: &gimli::DebuggingInformationEntry<EndianSlice<RunTimeEndian>>,
+) -> Option<String> {
+    let name = match entry.attr_value(gimli::constants::DW_AT_name)? {
+        gimli::AttributeValue::String(name) => name,
+        gimli::AttributeValue::DebugStrRef(offset) => input_dwarf.debug_str.get_str(offset).ok()?,
+        _ => return None,
+    };
+    Some(name.to_string().ok()?.to_string())
+}
+
+// get the name of a compile unit, which is usually the path of the source file
+fn get_unit_name(unit: &gimli::Unit<EndianSlice<RunTimeEndian>>) -> Option<String> {
+    let name = unit.name?;
+    Some(name.to_string().ok()?.to_string())
+}
+
+fn obfuscate_unit(
+    context: &AttributeContext,
+    in_abbrevs: &gimli::Abbreviations,
+    output_unit: &mut gimli::write::Unit,
+    obfuscated_strings: &mut HashMap<String, String>,
+    report: &mut Report,
+) {
+    let encoding = output_unit.encoding();
+    let big_endian = version::is_big_endian(context.input_dwarf);
+    // depth of the namespace std entry, while its children are processed
+    let mut std_depth = None;
+    let mut entries_cursor = context.input_unit.entries(in_abbrevs);
+    while let Ok(Some(entry)) = entries_cursor.next_dfs() {
+        let depth = entry.depth();
+        if std_depth.is_some_and(|std_depth| depth <= std_depth) {
+            std_depth = None;
+        }
+        if context.keep_std
+            && std_depth.is_none()
+            && entry.tag() == gimli::constants::DW_TAG_namespace
+            && get_entry_name(context.input_dwarf, entry).as_deref() == Some("std")
+        {
+            std_depth = Some(depth);
+        }
+        let Some(id) = context.unit_offsets.get(&entry.offset().0) else {
+            // the entry was pruned, or it is a duplicate of an entry in another unit
+            continue;
+        };
+        // get the entry in the output unit
+        let output_entry = output_unit.get_mut(*id);
+
+        // process attributes
+        for attr in entry.attrs() {
+            if attr.name() == gimli::constants::DW_AT_sibling {
+                // gimli generates the sibling references itself
+                output_entry.set_sibling(true);
+                continue;
+            }
+            if context.scrub_source != ScrubSource::Keep && scrub::is_source_metadata(attr.name()) {
+                if let Some(output_value) =
+                    scrub::scrubbed_value(context.scrub_source, context.line_programs, attr.name())
+                {
+                    output_entry.set(attr.name(), output_value);
+                }
+                continue;
+            }
+            // some attributes changed their meaning between the DWARF versions
+            if let Some(attributes) = version::replaced_attributes(
+                context.input_dwarf,
+                context.input_unit,
+                in_abbrevs,
+                entry,
+                attr.name(),
+                encoding,
+            ) {
+                for (name, value) in attributes {
+                    output_entry.set(name, value);
+                }
+                continue;
+            }
+            let Some(policy) = policy::attribute_policy(attr.name()) else {
+                report.add_dropped_attribute(policy::attribute_name(attr.name()));
+                continue;
+            };
+            if policy == AttributePolicy::Drop {
+                continue;
+            }
+            let Some(mut output_value) = obfuscate_attribute_value(
+                context,
+                attr.name(),
+                policy,
+                attr.value(),
+                std_depth.is_some(),
+                obfuscated_strings,
+                report,
+            ) else {
+                continue;
+            };
+            if policy == AttributePolicy::Constant && scrub::is_scrubbed_constant(entry.tag()) {
+                let Some(value) = scrub::scrubbed_constant(context.const_values, output_value)
+                else {
+                    continue;
+                };
+                output_value = value;
+            }
+            if let Some((name, value)) =
+                version::convert_attribute(encoding, big_endian, attr.name(), output_value)
+            {
+                output_entry.set(name, value);
+            }
+        }
+    }
+}
+
+fn obfuscate_attribute_value(
+    context: &AttributeContext,
+    attrtype: gimli::DwAt,
+    policy: AttributePolicy,
+    value: gimli::AttributeValue<SliceType>,
+    in_std: bool,
+    obfuscated_strings: &mut HashMap<String, String>,
+    report: &mut Report,
+) -> Option<gimli::write::AttributeValue> {
+    match value {
+        gimli::AttributeValue::Addr(address) if policy == AttributePolicy::Address => {
+            report.add_remapped_address();
+            Some(gimli::write::AttributeValue::Address(
+                gimli::write::Address::Constant(obfuscate_address(context, address)),
+            ))
+        }
+        gimli::AttributeValue::Addr(val) => Some(gimli::write::AttributeValue::Address(
+            gimli::write::Address::Constant(val),
+        )),
+        gimli::AttributeValue::Block(val) => {
+            Some(gimli::write::AttributeValue::Block(val.to_vec()))
+        }
+        gimli::AttributeValue::Data1(val) => Some(gimli::write::AttributeValue::Data1(val)),
+        gimli::AttributeValue::Data2(val) => Some(gimli::write::AttributeValue::Data2(val)),
+        gimli::AttributeValue::Data4(val) => Some(gimli::write::AttributeValue::Data4(val)),
+        gimli::AttributeValue::Data8(val) => Some(gimli::write::AttributeValue::Data8(val)),
+        gimli::AttributeValue::Data16(val) => Some(gimli::write::AttributeValue::Data16(val)),
+        gimli::AttributeValue::Sdata(val) => Some(gimli::write::AttributeValue::Sdata(val)),
+        gimli::AttributeValue::Udata(val) => Some(gimli::write::AttributeValue::Udata(val)),
+        gimli::AttributeValue::Exprloc(expression) if policy == AttributePolicy::Address => {
+            let address = evaluate_address(context, expression)?;
+            report.add_remapped_address();
+            let mut output_expr = gimli::write::Expression::new();
+            output_expr.op_addr(gimli::write::Address::Constant(obfuscate_address(
+                context, address,
+            )));
+            Some(gimli::write::AttributeValue::Exprloc(output_expr))
+        }
+        gimli::AttributeValue::Exprloc(expression) => {
+            // other expressions, e.g. DW_OP_plus_uconst in a DW_AT_data_member_location, are copied
+            // unless they contain addresses or references to other entries
+            is_self_contained(expression, context.input_unit.encoding()).then(|| {
+                gimli::write::AttributeValue::Exprloc(gimli::write::Expression::raw(
+                    expression.0.to_vec(),
+                ))
+            })
+        }
+        gimli::AttributeValue::Flag(flg) => Some(gimli::write::AttributeValue::Flag(flg)),
+        gimli::AttributeValue::SecOffset(offset)
+            if attrtype == gimli::constants::DW_AT_GNU_macros =>
+        {
+            let new_offset = context.macro_offsets.get(&offset)?;
+            Some(gimli::write::AttributeValue::DebugMacroRef(
+                gimli::DebugMacroOffset(*new_offset),
+            ))
+        }
+        // the location lists, range lists and address tables of the input are not part of the output
+        gimli::AttributeValue::SecOffset(_)
+        | gimli::AttributeValue::DebugAddrBase(_)
+        | gimli::AttributeValue::DebugAddrIndex(_)
+        | gimli::AttributeValue::DebugLineRef(_)
+        | gimli::AttributeValue::LocationListsRef(_)
+        | gimli::AttributeValue::DebugLocListsBase(_)
+        | gimli::AttributeValue::DebugLocListsIndex(_)
+        | gimli::AttributeValue::RangeListsRef(_)
+        | gimli::AttributeValue::DebugRngListsBase(_)
+        | gimli::AttributeValue::DebugRngListsIndex(_)
+        | gimli::AttributeValue::DebugStrOffsetsBase(_)
+        | gimli::AttributeValue::DebugLineStrRef(_)
+        | gimli::AttributeValue::FileIndex(_)
+        | gimli::AttributeValue::DwoId(_) => None,
+        gimli::AttributeValue::UnitRef(unit_offset) => {
+            // this is a reference to a unit, we need to find the corresponding entry id in the output unit
+            if let Some(entry_id) = context.unit_offsets.get(&unit_offset.0) {
+                Some(gimli::write::AttributeValue::UnitRef(*entry_id))
+            } else if let Some((unit_id, entry_id)) = unit_offset
+                .to_debug_info_offset(context.input_unit)
+                .and_then(|offset| context.debuginfo_offsets.get(&offset.0))
+            {
+                // the referenced entry is a duplicate type, which was replaced by an identical type.
+                // This type is either in the same (merged) unit or in an earlier unit
+                if *unit_id == context.unit_id {
+                    Some(gimli::write::AttributeValue::UnitRef(*entry_id))
+                } else {
+                    let reference = gimli::write::DebugInfoRef::Entry(*unit_id, *entry_id);
+                    Some(gimli::write::AttributeValue::DebugInfoRef(reference))
+                }
+            } else {
+                report.add_warning(format!(
+                    "the referenced entry {:#x} was not found, the reference was removed",
+                    unit_offset.0
+                ));
+                None
+            }
+        }
+        gimli::AttributeValue::DebugInfoRef(debug_info_offset) => {
+            if let Some((unit_id, entry_id)) = context.debuginfo_offsets.get(&debug_info_offset.0) {
+                let reference = gimli::write::DebugInfoRef::Entry(*unit_id, *entry_id);
+                Some(gimli::write::AttributeValue::DebugInfoRef(reference))
+            } else {
+                report.add_warning(format!(
+                    "the referenced entry at {:#x} in .debug_info was not found, the reference was removed",
+                    debug_info_offset.0
+                ));
+                None
+            }
+        }
+        // supplementary object files and type units are not read
+        gimli::AttributeValue::DebugInfoRefSup(_) => {
+            report.add_dropped_form("DW_FORM_ref_sup");
+            None
+        }
+        gimli::AttributeValue::DebugStrRefSup(_) => {
+            report.add_dropped_form("DW_FORM_strp_sup");
+            None
+        }
+        gimli::AttributeValue::DebugTypesRef(_) => {
+            report.add_dropped_form("DW_FORM_ref_sig8");
+            None
+        }
+        gimli::AttributeValue::DebugMacinfoRef(offset) => {
+            let new_offset = context.macinfo_offsets.get(&offset.0)?;
+            Some(gimli::write::AttributeValue::DebugMacinfoRef(
+                gimli::DebugMacinfoOffset(*new_offset),
+            ))
+        }
+        gimli::AttributeValue::DebugMacroRef(offset) => {
+            let new_offset = context.macro_offsets.get(&offset.0)?;
+            Some(gimli::write::AttributeValue::DebugMacroRef(
+                gimli::DebugMacroOffset(*new_offset),
+            ))
+        }
+        gimli::AttributeValue::DebugStrRef(debug_str_offset) => {
+            let strval = context
+                .input_dwarf
+                .debug_str
+                .get_str(debug_str_offset)
+                .ok()?;
+            let obfuscated = obfuscate_string_value(
+                context,
+                policy,
+                strval.to_string().ok()?,
+                in_std,
+                obfuscated_strings,
+            );
+            Some(gimli::write::AttributeValue::String(
+                obfuscated.into_bytes(),
+            ))
+        }
+        gimli::AttributeValue::DebugStrOffsetsIndex(index) => {
+            let debug_str_offset = context
+                .input_dwarf
+                .debug_str_offsets
+                .get_str_offset(context.input_unit.format(), context.str_offsets_base, index)
+                .ok()?;
+            let strval = context
+                .input_dwarf
+                .debug_str
+                .get_str(debug_str_offset)
+                .ok()?;
+            let obfuscated = obfuscate_string_value(
+                context,
+                policy,
+                strval.to_string().ok()?,
+                in_std,
+                obfuscated_strings,
+            );
+            Some(gimli::write::AttributeValue::String(
+                obfuscated.into_bytes(),
+            ))
+        }
+        gimli::AttributeValue::String(strval) => {
+            let obfuscated = obfuscate_string_value(
+                context,
+                policy,
+                strval.to_string().ok()?,
+                in_std,
+                obfuscated_strings,
+            );
+            Some(gimli::write::AttributeValue::String(
+                obfuscated.into_bytes(),
+            ))
+        }
+        gimli::AttributeValue::Encoding(dw_ate) => {
+            Some(gimli::write::AttributeValue::Encoding(dw_ate))
+        }
+        gimli::AttributeValue::DecimalSign(dw_ds) => {
+            Some(gimli::write::AttributeValue::DecimalSign(dw_ds))
+        }
+        gimli::AttributeValue::Endianity(dw_end) => {
+            Some(gimli::write::AttributeValue::Endianity(dw_end))
+        }
+        gimli::AttributeValue::Accessibility(dw_access) => {
+            Some(gimli::write::AttributeValue::Accessibility(dw_access))
+        }
+        gimli::AttributeValue::Visibility(dw_vis) => {
+            Some(gimli::write::AttributeValue::Visibility(dw_vis))
+        }
+        gimli::AttributeValue::Virtuality(dw_virtuality) => {
+            Some(gimli::write::AttributeValue::Virtuality(dw_virtuality))
+        }
+        gimli::AttributeValue::Language(dw_lang) => {
+            Some(gimli::write::AttributeValue::Language(dw_lang))
+        }
+        gimli::AttributeValue::AddressClass(dw_addr) => {
+            Some(gimli::write::AttributeValue::AddressClass(dw_addr))
+        }
+        gimli::AttributeValue::IdentifierCase(dw_id) => {
+            Some(gimli::write::AttributeValue::IdentifierCase(dw_id))
+        }
+        gimli::AttributeValue::CallingConvention(dw_cc) => {
+            Some(gimli::write::AttributeValue::CallingConvention(dw_cc))
+        }
+        gimli::AttributeValue::Inline(dw_inl) => Some(gimli::write::AttributeValue::Inline(dw_inl)),
+        gimli::AttributeValue::Ordering(dw_ord) => {
+            Some(gimli::write::AttributeValue::Ordering(dw_ord))
+        }
+    }
+}
+
+// names are obfuscated like the names of the entries they refer to. Other strings are never copied,
+// because they might contain names too; they are replaced consistently through the mapping table
+fn obfuscate_string_value(
+    context: &AttributeContext,
+    policy: AttributePolicy,
+    value: &str,
+    in_std: bool,
+    obfuscated_strings: &mut HashMap<String, String>,
+) -> String {
+    if policy == AttributePolicy::Name {
+        obfuscate_entry_name(
+            value,
+            (context.keep_std, in_std),
+            context.keep_list,
+            obfuscated_strings,
+        )
+    } else {
+        mapped_name(value, context.keep_list, obfuscated_strings)
+    }
+}
+
+// evaluate a location expression that results in the address of a variable
+fn evaluate_address(
+    context: &AttributeContext,
+    expression: gimli::Expression<SliceType>,
+) -> Option<u64> {
+    let mut evaluation = expression.evaluation(context.input_unit.encoding());
+    evaluation.set_object_address(0);
+    evaluation.set_initial_value(0);
+    evaluation.set_max_iterations(100);
+    let mut eval_result = evaluation.evaluate().ok()?;
+    while eval_result != gimli::EvaluationResult::Complete {
+        match eval_result {
+            gimli::EvaluationResult::RequiresRelocatedAddress(address) => {
+                // assume that there is no relocation
+                // this would be a bad bet on PC, but on embedded controllers where A2l files are used this is the standard
+                eval_result = evaluation.resume_with_relocated_address(address).ok()?;
+            }
+            // e.g. local variables, which need the frame base
+            _ => return None,
+        }
+    }
+    match evaluation.result().first() {
+        Some(gimli::Piece {
+            location: gimli::Location::Address { address },
+            ..
+        }) => Some(*address),
+        _ => None,
+    }
+}
+
+// get the new address of a variable
+fn obfuscate_address(context: &AttributeContext, address: u64) -> u64 {
+    // variables in the kept data sections move together with their section
+    context
+        .address_layout
+        .translate(address)
+        .unwrap_or_else(|| {
+            // we're not worried about the content of the lower 8 bits of the address;
+            // keeping them preserves alignment, and makes the resulting address more realistic
+            (rand::random::<u64>() & 0xffff_ff00) | (address & 0x0000_00ff)
+        })
+}
+
+// check if an expression can be copied unchanged: it must not contain addresses, which are
+// randomized, or references to entries and indexes into other sections, which change
+fn is_self_contained(expression: gimli::Expression<SliceType>, encoding: gimli::Encoding) -> bool {
+    let mut operations = expression.operations(encoding);
+    loop {
+        match operations.next() {
+            Ok(None) => return true,
+            Err(_)
+            | Ok(Some(
+                gimli::Operation::Address { .. }
+                | gimli::Operation::AddressIndex { .. }
+                | gimli::Operation::ConstantIndex { .. }
+                | gimli::Operation::Call { .. }
+                | gimli::Operation::VariableValue { .. }
+                | gimli::Operation::ImplicitPointer { .. }
+                | gimli::Operation::EntryValue { .. }
+                | gimli::Operation::ParameterRef { .. }
+                | gimli::Operation::TypedLiteral { .. }
+                | gimli::Operation::Convert { .. }
+                | gimli::Operation::Reinterpret { .. },
+            )) => return false,
+            Ok(Some(
+                gimli::Operation::Deref { base_type, .. }
+                | gimli::Operation::RegisterOffset { base_type, .. },
+            )) if base_type.0 != 0 => return false,
+            Ok(Some(_)) => {}
+        }
+    }
+}
+
+/// get the obfuscated version of the name or linkage name of an entry.
+/// Mangled names and template names are obfuscated piece by piece, so that the identifiers they
+/// contain are consistent with the obfuscated names of the entries they refer to.
+/// The names of entries inside of namespace std (in_std) are kept if keep_std is set
+fn obfuscate_entry_name(
+    name: &str,
+    (keep_std, in_std): (bool, bool),
+    keep_list: &KeepList,
+    obfuscated_strings: &mut HashMap<String, String>,
+) -> String {
+    if keep_list.contains(name) {
+        name.to_string()
+    } else if let Some(obfuscated) =
+        mangling::obfuscate_mangled_name(name, keep_std, keep_list, obfuscated_strings)
+    {
+        obfuscated
+    } else if name.contains('<') {
+        typename::obfuscate_type_name(name, (keep_std, in_std), keep_list, obfuscated_strings)
+    } else if keep_std && (in_std || name == "std") {
+        name.to_string()
+    } else {
+        mapped_name(name, keep_list, obfuscated_strings)
+    }
+}
+
+/// get the obfuscated version of a name from the mapping table, or add a new one.
+/// This ensures that the same string is always obfuscated to the same value.
+/// Names on the keep list are mapped to themselves
+fn mapped_name(
+    name: &str,
+    keep_list: &KeepList,
+    obfuscated_strings: &mut HashMap<String, String>,
+) -> String {
+    obfuscated_strings
+        .entry(name.to_string())
+        .or_insert_with(|| {
+            if keep_list.contains(name) {
+                name.to_string()
+            } else {
+                obfuscate_name(name)
+            }
+        })
+        .clone()
+}
+
+// obfuscate a name by replacing it with a random string of the same length
+fn obfuscate_name(name: &str) -> String {
+    static CHARLIST: &[u8] = b"abcdefghijklmnopqrstuvwxyzABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789_";
+    let mut obfuscated_name = String::with_capacity(name.len());
+    obfuscated_name.push((b'a' + rand::random_range(0..26)) as char);
+    for _ in 1..name.len() {
+        obfuscated_name.push(CHARLIST[rand::random_range(0..CHARLIST.len())] as char);
+    }
+    obfuscated_name
+}
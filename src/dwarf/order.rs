@@ -0,0 +1,100 @@
+use super::obfuscate_entry_name;
+use crate::keeplist::KeepList;
+use crate::options::OutputOrder;
+use gimli::{DwTag, write::UnitEntryId};
+use rand::{rngs::StdRng, seq::SliceRandom};
+use std::collections::HashMap;
+
+/// an entry whose id has been reserved in the output unit, but which has not been added to its parent yet
+pub(super) struct PendingEntry {
+    pub(super) parent: UnitEntryId,
+    pub(super) id: UnitEntryId,
+    pub(super) tag: DwTag,
+    pub(super) name: Option<String>,
+    /// the entry is inside of namespace std
+    pub(super) in_std: bool,
+}
+
+/// add the pending entries to the output unit.
+/// Siblings are added in the requested order, but only if their order carries no meaning:
+/// struct members, function parameters, array dimensions etc. always keep their original order
+pub(super) fn add_pending_entries(
+    output_unit: &mut gimli::write::Unit,
+    pending_entries: Vec<PendingEntry>,
+    order: OutputOrder,
+    keep_std: bool,
+    keep_list: &KeepList,
+    rng: &mut StdRng,
+    obfuscated_strings: &mut HashMap<String, String>,
+) {
+    let mut tags = HashMap::<UnitEntryId, DwTag>::new();
+    tags.insert(output_unit.root(), gimli::constants::DW_TAG_compile_unit);
+
+    // group the entries by their parent, keeping the original order of the parents
+    let mut parents = Vec::<UnitEntryId>::new();
+    let mut children = HashMap::<UnitEntryId, Vec<PendingEntry>>::new();
+    for pending in pending_entries {
+        tags.insert(pending.id, pending.tag);
+        let siblings = children.entry(pending.parent).or_default();
+        if siblings.is_empty() {
+            parents.push(pending.parent);
+        }
+        siblings.push(pending);
+    }
+
+    for parent in parents {
+        let mut siblings = children.remove(&parent).unwrap_or_default();
+        if tags
+            .get(&parent)
+            .is_some_and(|tag| has_unordered_children(*tag))
+        {
+            reorder(&mut siblings, order, rng, |pending| {
+                obfuscated_sort_key(
+                    pending.name.as_deref(),
+                    (keep_std, pending.in_std),
+                    keep_list,
+                    obfuscated_strings,
+                )
+            });
+        }
+        for pending in siblings {
+            output_unit.add_reserved(pending.id, pending.parent, pending.tag);
+        }
+    }
+}
+
+/// reorder a list of items according to the requested output order
+pub(super) fn reorder<T>(
+    items: &mut [T],
+    order: OutputOrder,
+    rng: &mut StdRng,
+    mut sort_key: impl FnMut(&T) -> String,
+) {
+    match order {
+        OutputOrder::Original => {}
+        OutputOrder::Shuffle => items.shuffle(rng),
+        OutputOrder::Sort => items.sort_by_cached_key(|item| sort_key(item)),
+    }
+}
+
+/// get the obfuscated version of a name, which is used to sort the output.
+/// The obfuscated name is stored, so that the same name is used when the attributes are written
+pub(super) fn obfuscated_sort_key(
+    name: Option<&str>,
+    (keep_std, in_std): (bool, bool),
+    keep_list: &KeepList,
+    obfuscated_strings: &mut HashMap<String, String>,
+) -> String {
+    let Some(name) = name else {
+        return String::new();
+    };
+    obfuscate_entry_name(name, (keep_std, in_std), keep_list, obfuscated_strings)
+}
+
+// the children of these entries can be placed in any order
+fn has_unordered_children(tag: DwTag) -> bool {
+    tag == gimli::constants::DW_TAG_compile_unit
+        || tag == gimli::constants::DW_TAG_partial_unit
+        || tag == gimli::constants::DW_TAG_namespace
+        || tag == gimli::constants::DW_TAG_enumeration_type
+}
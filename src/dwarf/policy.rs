@@ -0,0 +1,143 @@
+use gimli::{DwAt, constants};
+
+/// how the value of an attribute is handled in the output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum AttributePolicy {
+    /// copy the value; references are redirected to the output entries, and strings are obfuscated
+    Copy,
+    /// remove the attribute
+    Drop,
+    /// the value is the address of a variable, which is randomized
+    Address,
+    /// the value is the name of the entry, which is obfuscated
+    Name,
+    /// the value is a constant, which might be calibration data; it is handled according to --const-values
+    Constant,
+}
+
+// attributes that are not handled by their form alone. Other standard attributes are copied,
+// and other vendor attributes are dropped, because nothing is known about their content
+const ATTRIBUTE_POLICIES: &[(DwAt, AttributePolicy)] = &[
+    (constants::DW_AT_name, AttributePolicy::Name),
+    (constants::DW_AT_linkage_name, AttributePolicy::Name),
+    (constants::DW_AT_location, AttributePolicy::Address),
+    (constants::DW_AT_const_value, AttributePolicy::Constant),
+    (constants::DW_AT_default_value, AttributePolicy::Constant),
+    (constants::DW_AT_producer, AttributePolicy::Drop),
+    (constants::DW_AT_comp_dir, AttributePolicy::Drop),
+    // address spaces, e.g. of TriCore and PowerPC compilers
+    (constants::DW_AT_address_class, AttributePolicy::Copy),
+    (constants::DW_AT_segment, AttributePolicy::Copy),
+    // MIPS / SGI
+    (constants::DW_AT_MIPS_linkage_name, AttributePolicy::Name),
+    (constants::DW_AT_MIPS_abstract_name, AttributePolicy::Name),
+    (constants::DW_AT_MIPS_fde, AttributePolicy::Drop),
+    (constants::DW_AT_MIPS_loop_begin, AttributePolicy::Drop),
+    (constants::DW_AT_MIPS_tail_loop_begin, AttributePolicy::Drop),
+    (constants::DW_AT_MIPS_epilog_begin, AttributePolicy::Drop),
+    (
+        constants::DW_AT_MIPS_loop_unroll_factor,
+        AttributePolicy::Drop,
+    ),
+    (
+        constants::DW_AT_MIPS_software_pipeline_depth,
+        AttributePolicy::Drop,
+    ),
+    (constants::DW_AT_MIPS_clone_origin, AttributePolicy::Drop),
+    (constants::DW_AT_MIPS_has_inlines, AttributePolicy::Drop),
+    (constants::DW_AT_MIPS_stride, AttributePolicy::Copy),
+    (constants::DW_AT_MIPS_stride_byte, AttributePolicy::Copy),
+    (constants::DW_AT_MIPS_stride_elem, AttributePolicy::Copy),
+    // GNU
+    (constants::DW_AT_sf_names, AttributePolicy::Drop),
+    (constants::DW_AT_body_begin, AttributePolicy::Drop),
+    (constants::DW_AT_body_end, AttributePolicy::Drop),
+    (constants::DW_AT_GNU_vector, AttributePolicy::Copy),
+    (constants::DW_AT_GNU_guarded_by, AttributePolicy::Drop),
+    (constants::DW_AT_GNU_pt_guarded_by, AttributePolicy::Drop),
+    (constants::DW_AT_GNU_guarded, AttributePolicy::Drop),
+    (constants::DW_AT_GNU_pt_guarded, AttributePolicy::Drop),
+    (constants::DW_AT_GNU_locks_excluded, AttributePolicy::Drop),
+    (
+        constants::DW_AT_GNU_exclusive_locks_required,
+        AttributePolicy::Drop,
+    ),
+    (
+        constants::DW_AT_GNU_shared_locks_required,
+        AttributePolicy::Drop,
+    ),
+    // a hash of the names in the type, which would allow to recognize the type
+    (constants::DW_AT_GNU_odr_signature, AttributePolicy::Drop),
+    (constants::DW_AT_GNU_template_name, AttributePolicy::Name),
+    (constants::DW_AT_GNU_call_site_value, AttributePolicy::Drop),
+    (
+        constants::DW_AT_GNU_call_site_data_value,
+        AttributePolicy::Drop,
+    ),
+    (constants::DW_AT_GNU_call_site_target, AttributePolicy::Drop),
+    (
+        constants::DW_AT_GNU_call_site_target_clobbered,
+        AttributePolicy::Drop,
+    ),
+    (constants::DW_AT_GNU_tail_call, AttributePolicy::Drop),
+    (
+        constants::DW_AT_GNU_all_tail_call_sites,
+        AttributePolicy::Drop,
+    ),
+    (constants::DW_AT_GNU_all_call_sites, AttributePolicy::Drop),
+    (
+        constants::DW_AT_GNU_all_source_call_sites,
+        AttributePolicy::Drop,
+    ),
+    (constants::DW_AT_GNU_macros, AttributePolicy::Copy),
+    (constants::DW_AT_GNU_deleted, AttributePolicy::Copy),
+    // the split debug info is merged into the output
+    (constants::DW_AT_GNU_dwo_name, AttributePolicy::Drop),
+    (constants::DW_AT_GNU_dwo_id, AttributePolicy::Drop),
+    (constants::DW_AT_GNU_ranges_base, AttributePolicy::Drop),
+    (constants::DW_AT_GNU_addr_base, AttributePolicy::Drop),
+    (constants::DW_AT_GNU_pubnames, AttributePolicy::Drop),
+    (constants::DW_AT_GNU_pubtypes, AttributePolicy::Drop),
+    (constants::DW_AT_GNU_discriminator, AttributePolicy::Drop),
+    (constants::DW_AT_GNU_locviews, AttributePolicy::Drop),
+    (constants::DW_AT_GNU_entry_view, AttributePolicy::Drop),
+    (constants::DW_AT_GNU_numerator, AttributePolicy::Copy),
+    (constants::DW_AT_GNU_denominator, AttributePolicy::Copy),
+    (constants::DW_AT_GNU_bias, AttributePolicy::Copy),
+    // LLVM and Apple: mostly command line options and paths
+    (constants::DW_AT_LLVM_include_path, AttributePolicy::Drop),
+    (constants::DW_AT_LLVM_config_macros, AttributePolicy::Drop),
+    (constants::DW_AT_LLVM_isysroot, AttributePolicy::Drop),
+    (constants::DW_AT_APPLE_optimized, AttributePolicy::Drop),
+    (constants::DW_AT_APPLE_flags, AttributePolicy::Drop),
+    (constants::DW_AT_APPLE_isa, AttributePolicy::Drop),
+    (constants::DW_AT_APPLE_omit_frame_ptr, AttributePolicy::Drop),
+    // Sun / Oracle
+    (constants::DW_AT_SUN_command_line, AttributePolicy::Drop),
+    (constants::DW_AT_SUN_compile_options, AttributePolicy::Drop),
+    (constants::DW_AT_SUN_link_name, AttributePolicy::Name),
+    (constants::DW_AT_SUN_alignment, AttributePolicy::Copy),
+    (constants::DW_AT_upc_threads_scaled, AttributePolicy::Copy),
+];
+
+/// get the policy for an attribute. Returns None for vendor attributes with an unknown meaning
+pub(super) fn attribute_policy(attrtype: DwAt) -> Option<AttributePolicy> {
+    if let Some((_, policy)) = ATTRIBUTE_POLICIES
+        .iter()
+        .find(|(policy_attr, _)| *policy_attr == attrtype)
+    {
+        Some(*policy)
+    } else if attrtype.0 < constants::DW_AT_lo_user.0 {
+        Some(AttributePolicy::Copy)
+    } else {
+        None
+    }
+}
+
+/// the name of an attribute, for reports
+pub(super) fn attribute_name(attrtype: DwAt) -> String {
+    match attrtype.static_string() {
+        Some(name) => name.to_string(),
+        None => format!("DW_AT_{:#x}", attrtype.0),
+    }
+}
@@ -0,0 +1,171 @@
+use crate::options::{ConstValues, LinePrograms, ScrubSource};
+use gimli::{DwAt, DwLang, DwTag, write::AttributeValue};
+use std::collections::HashSet;
+
+/// attributes that describe where in the source code an entry was declared, and how it was compiled
+pub(super) fn is_source_metadata(attrtype: DwAt) -> bool {
+    attrtype == gimli::constants::DW_AT_decl_file
+        || attrtype == gimli::constants::DW_AT_decl_line
+        || attrtype == gimli::constants::DW_AT_decl_column
+        || attrtype == gimli::constants::DW_AT_call_file
+        || attrtype == gimli::constants::DW_AT_call_line
+        || attrtype == gimli::constants::DW_AT_call_column
+        || attrtype == gimli::constants::DW_AT_comp_dir
+        || attrtype == gimli::constants::DW_AT_producer
+}
+
+/// the replacement value of a source metadata attribute; None if the attribute is removed
+pub(super) fn scrubbed_value(
+    scrub_source: ScrubSource,
+    line_programs: LinePrograms,
+    attrtype: DwAt,
+) -> Option<gimli::write::AttributeValue> {
+    if scrub_source != ScrubSource::Normalize {
+        return None;
+    }
+    // columns, the compilation directory and the producer are optional, and are always removed.
+    // File indices refer to the line program, so they can only be kept if there is one
+    let is_file = attrtype == gimli::constants::DW_AT_decl_file
+        || attrtype == gimli::constants::DW_AT_call_file;
+    let is_line = attrtype == gimli::constants::DW_AT_decl_line
+        || attrtype == gimli::constants::DW_AT_call_line;
+    if is_line || (is_file && line_programs == LinePrograms::Synthetic) {
+        Some(gimli::write::AttributeValue::Udata(1))
+    } else {
+        None
+    }
+}
+
+/// check if the constant values of an entry can be removed or randomized. Enumerator values are
+/// needed to check the COMPU_VTABs of the a2l file, and the values of template parameters are
+/// part of the type names anyway
+pub(super) fn is_scrubbed_constant(tag: DwTag) -> bool {
+    tag != gimli::constants::DW_TAG_enumerator
+        && tag != gimli::constants::DW_TAG_template_value_parameter
+}
+
+/// the replacement of a DW_AT_const_value or DW_AT_default_value; None if the attribute is removed.
+/// Random values have the same size as the original values, references to other entries are kept
+pub(super) fn scrubbed_constant(
+    const_values: ConstValues,
+    value: AttributeValue,
+) -> Option<AttributeValue> {
+    match const_values {
+        ConstValues::Keep => Some(value),
+        ConstValues::Remove => None,
+        ConstValues::Randomize => Some(match value {
+            AttributeValue::Data1(_) => AttributeValue::Data1(rand::random()),
+            AttributeValue::Data2(_) => AttributeValue::Data2(rand::random()),
+            AttributeValue::Data4(_) => AttributeValue::Data4(rand::random()),
+            AttributeValue::Data8(_) => AttributeValue::Data8(rand::random()),
+            AttributeValue::Data16(_) => AttributeValue::Data16(rand::random()),
+            AttributeValue::Udata(val) => {
+                let mask = u64::MAX.checked_shr(val.leading_zeros()).unwrap_or(0);
+                AttributeValue::Udata(rand::random::<u64>() & mask)
+            }
+            AttributeValue::Sdata(val) => {
+                // the arithmetic shift keeps the number of significant bits, including the sign
+                let sign_bits = if val < 0 {
+                    val.leading_ones()
+                } else {
+                    val.leading_zeros()
+                };
+                AttributeValue::Sdata(rand::random::<i64>() >> sign_bits.saturating_sub(1))
+            }
+            AttributeValue::Block(bytes) => {
+                AttributeValue::Block(bytes.iter().map(|_| rand::random()).collect())
+            }
+            // strings are already obfuscated, and references point to obfuscated entries
+            value => value,
+        }),
+    }
+}
+
+/// create a plausible file name for a compile unit, e.g. "lokari_temu.c"
+pub(super) fn synthetic_unit_name(
+    language: Option<DwLang>,
+    used_names: &mut HashSet<String>,
+) -> String {
+    let extension = match language {
+        Some(
+            gimli::constants::DW_LANG_C_plus_plus
+            | gimli::constants::DW_LANG_C_plus_plus_03
+            | gimli::constants::DW_LANG_C_plus_plus_11
+            | gimli::constants::DW_LANG_C_plus_plus_14
+            | gimli::constants::DW_LANG_C_plus_plus_17
+            | gimli::constants::DW_LANG_C_plus_plus_20,
+        ) => "cpp",
+        Some(gimli::constants::DW_LANG_Mips_Assembler) => "s",
+        _ => "c",
+    };
+
+    synthetic_file_name(extension, used_names)
+}
+
+/// Create a line program for a unit, which contains synthetic file names but no line information.
+/// The file numbers in the DW_AT_decl_file attributes remain valid, because the line program
+/// has as many files as the original line program
+pub(super) fn synthetic_line_program(
+    output_unit: &gimli::write::Unit,
+    file_count: usize,
+) -> gimli::write::LineProgram {
+    let encoding = output_unit.encoding();
+    let root = output_unit.get(output_unit.root());
+    let mut used_names = HashSet::new();
+    let unit_name = match root.get(gimli::constants::DW_AT_name) {
+        Some(gimli::write::AttributeValue::String(name)) => name.clone(),
+        _ => synthetic_file_name("c", &mut used_names).into_bytes(),
+    };
+
+    let mut program = gimli::write::LineProgram::new(
+        encoding,
+        gimli::LineEncoding::default(),
+        gimli::write::LineString::String(b"src".to_vec()),
+        None,
+        gimli::write::LineString::String(unit_name),
+        None,
+    );
+    let directory = program.default_directory();
+    // in DWARF 5 the unit's own file is file 0, and it was added by LineProgram::new
+    let first_file = if encoding.version >= 5 { 1 } else { 0 };
+    for _ in first_file..file_count {
+        let file_name = synthetic_file_name("h", &mut used_names);
+        program.add_file(
+            gimli::write::LineString::String(file_name.into_bytes()),
+            directory,
+            None,
+        );
+    }
+    // an empty sequence; the line program is only written if it contains at least one instruction
+    program.begin_sequence(Some(gimli::write::Address::Constant(0)));
+    program.end_sequence(0);
+    program
+}
+
+// create a plausible file name with the given extension that has not been used before
+fn synthetic_file_name(extension: &str, used_names: &mut HashSet<String>) -> String {
+    loop {
+        let mut name = make_word();
+        if rand::random_bool(0.5) {
+            name.push('_');
+            name.push_str(&make_word());
+        }
+        name.push('.');
+        name.push_str(extension);
+        if used_names.insert(name.clone()) {
+            return name;
+        }
+    }
+}
+
+// a pronounceable random word of 2 to 4 syllables
+fn make_word() -> String {
+    static CONSONANTS: &[u8] = b"bcdfghklmnprstvz";
+    static VOWELS: &[u8] = b"aeiou";
+    let mut word = String::new();
+    for _ in 0..rand::random_range(2..=4) {
+        word.push(CONSONANTS[rand::random_range(0..CONSONANTS.len())] as char);
+        word.push(VOWELS[rand::random_range(0..VOWELS.len())] as char);
+    }
+    word
+}
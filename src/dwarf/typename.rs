@@ -0,0 +1,248 @@
+use super::mapped_name;
+use crate::keeplist::KeepList;
+use std::collections::{HashMap, HashSet};
+
+// words that can occur in a type name, but which are not user identifiers
+pub(super) static KEYWORDS: &[&str] = &[
+    "_Bool", "__int128", "bool", "char", "char8_t", "char16_t", "char32_t", "class", "const",
+    "decltype", "double", "enum", "false", "float", "int", "long", "nullptr", "operator", "short",
+    "signed", "sizeof", "struct", "true", "union", "unsigned", "void", "volatile", "wchar_t",
+];
+
+/// Obfuscate a type name that contains template arguments, e.g. "Filter<float, 8>".
+/// Only the user identifiers are replaced, using the same mapping as all other names, so that
+/// "Filter<Calib, 8>" becomes "xYz12a<Qw3Ab, 8>" when "Filter" -> "xYz12a" and "Calib" -> "Qw3Ab".
+/// Keywords, numbers, punctuation and names on the keep list are kept. If keep_std is set, then the names of
+/// standard library entities ("std::array") are kept as well; in_std indicates that the
+/// name itself belongs to an entity inside of namespace std.
+pub(super) fn obfuscate_type_name(
+    name: &str,
+    (keep_std, in_std): (bool, bool),
+    keep_list: &KeepList,
+    obfuscated_strings: &mut HashMap<String, String>,
+) -> String {
+    let mut output = String::with_capacity(name.len());
+    let mut std_chain = keep_std && in_std;
+    for token in tokenize(name) {
+        match token {
+            Token::Identifier(identifier, std_entity) => {
+                if KEYWORDS.contains(&identifier) || (keep_std && (std_entity || std_chain)) {
+                    output.push_str(identifier);
+                } else {
+                    output.push_str(&mapped_name(identifier, keep_list, obfuscated_strings));
+                }
+                std_chain = false;
+            }
+            Token::Other(text) => output.push_str(text),
+        }
+    }
+    output
+}
+
+/// get all identifiers in a (demangled) name that belong to standard library entities
+pub(super) fn std_identifiers(name: &str) -> HashSet<&str> {
+    tokenize(name)
+        .into_iter()
+        .filter_map(|token| match token {
+            Token::Identifier(identifier, true) => Some(identifier),
+            _ => None,
+        })
+        .collect()
+}
+
+enum Token<'a> {
+    /// an identifier, and whether it is part of a qualified name that starts with "std::"
+    Identifier(&'a str, bool),
+    /// numbers, punctuation and whitespace
+    Other(&'a str),
+}
+
+fn tokenize(name: &str) -> Vec<Token<'_>> {
+    let bytes = name.as_bytes();
+    let mut tokens = vec![];
+    let mut in_std_name = false;
+    // for each open template argument list: was it opened after the name of a std entity?
+    let mut template_args = vec![];
+    let mut after_std_entity = false;
+    let mut pos = 0;
+    while pos < bytes.len() {
+        let start = pos;
+        if bytes[pos].is_ascii_alphabetic() || bytes[pos] == b'_' {
+            while pos < bytes.len() && (bytes[pos].is_ascii_alphanumeric() || bytes[pos] == b'_') {
+                pos += 1;
+            }
+            let identifier = &name[start..pos];
+            let qualified = name[pos..].starts_with("::");
+            let std_entity = in_std_name || (identifier == "std" && qualified);
+            tokens.push(Token::Identifier(identifier, std_entity));
+            in_std_name = std_entity && qualified;
+            after_std_entity = std_entity;
+            continue;
+        } else if bytes[pos].is_ascii_digit() {
+            // numbers, including suffixes like 8u or hex digits
+            while pos < bytes.len() && (bytes[pos].is_ascii_alphanumeric() || bytes[pos] == b'_') {
+                pos += 1;
+            }
+            tokens.push(Token::Other(&name[start..pos]));
+        } else if let Some(anonymous) = anonymous_scope(&name[pos..]) {
+            // "(anonymous namespace)" is a fixed text; clang writes "(anonymous struct at file.c:12:3)",
+            // but the location of the declaration is not kept
+            pos += anonymous.len();
+            let text = anonymous.split(" at ").next().unwrap_or(anonymous);
+            tokens.push(Token::Other(text));
+            if text.len() < anonymous.len() {
+                tokens.push(Token::Other(")"));
+            }
+        } else {
+            // punctuation and whitespace. "::" continues a qualified name, also after the
+            // template arguments of a std entity, e.g. std::array<int, 3>::size
+            pos += name[pos..].chars().next().map_or(1, char::len_utf8);
+            match bytes[start] {
+                b'<' => template_args.push(after_std_entity),
+                b'>' => {
+                    in_std_name =
+                        template_args.pop() == Some(true) && name[pos..].starts_with("::");
+                }
+                b':' if in_std_name => {}
+                _ => in_std_name = false,
+            }
+            tokens.push(Token::Other(&name[start..pos]));
+        }
+        after_std_entity = false;
+    }
+    tokens
+}
+
+// "(anonymous namespace)", "(anonymous struct)", "(anonymous union at file.c:3:5)" etc.
+fn anonymous_scope(text: &str) -> Option<&str> {
+    if !text.starts_with("(anonymous ") {
+        return None;
+    }
+    let end = text.find(')')?;
+    Some(&text[..=end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn obfuscate(name: &str, keep_std: (bool, bool)) -> (String, HashMap<String, String>) {
+        let mut obfuscated_strings = HashMap::new();
+        let result = obfuscate_type_name(
+            name,
+            keep_std,
+            &KeepList::new(true),
+            &mut obfuscated_strings,
+        );
+        (result, obfuscated_strings)
+    }
+
+    #[test]
+    fn template_with_number() {
+        let (result, obfuscated_strings) = obfuscate("Filter<float, 8>", (false, false));
+        assert_eq!(obfuscated_strings.len(), 1);
+        assert_eq!(
+            result,
+            format!("{}<float, 8>", obfuscated_strings["Filter"])
+        );
+
+        let (result, obfuscated_strings) = obfuscate("Filter<unsigned int, 0x10u>", (false, false));
+        assert_eq!(
+            result,
+            format!("{}<unsigned int, 0x10u>", obfuscated_strings["Filter"])
+        );
+    }
+
+    #[test]
+    fn std_template() {
+        let (result, obfuscated_strings) = obfuscate("std::array<Calib, 16>", (true, false));
+        assert_eq!(obfuscated_strings.len(), 1);
+        assert_eq!(
+            result,
+            format!("std::array<{}, 16>", obfuscated_strings["Calib"])
+        );
+
+        // without keep_std, std and array are ordinary names
+        let (result, obfuscated_strings) = obfuscate("std::array<Calib, 16>", (false, false));
+        assert_eq!(obfuscated_strings.len(), 3);
+        assert_eq!(
+            result,
+            format!(
+                "{}::{}<{}, 16>",
+                obfuscated_strings["std"], obfuscated_strings["array"], obfuscated_strings["Calib"]
+            )
+        );
+    }
+
+    #[test]
+    fn nested_templates() {
+        // the second '>' of ">>" closes the arguments of std::map, so iterator is a std entity
+        let (result, obfuscated_strings) =
+            obfuscate("std::map<int, std::vector<Calib>>::iterator", (true, false));
+        assert_eq!(obfuscated_strings.len(), 1);
+        assert_eq!(
+            result,
+            format!(
+                "std::map<int, std::vector<{}>>::iterator",
+                obfuscated_strings["Calib"]
+            )
+        );
+
+        // a member of a user template is not a std entity, even if an argument is
+        let (result, obfuscated_strings) =
+            obfuscate("Outer<std::vector<Calib>>::value", (true, false));
+        assert_eq!(
+            result,
+            format!(
+                "{}<std::vector<{}>>::{}",
+                obfuscated_strings["Outer"],
+                obfuscated_strings["Calib"],
+                obfuscated_strings["value"]
+            )
+        );
+    }
+
+    #[test]
+    fn name_in_std() {
+        // with keep_std, the leading name of an entity inside of namespace std is kept
+        let (result, obfuscated_strings) = obfuscate("allocator<Calib>", (true, true));
+        assert_eq!(
+            result,
+            format!("allocator<{}>", obfuscated_strings["Calib"])
+        );
+
+        for keep_std in [(true, false), (false, true)] {
+            let (result, obfuscated_strings) = obfuscate("allocator<Calib>", keep_std);
+            assert_eq!(
+                result,
+                format!(
+                    "{}<{}>",
+                    obfuscated_strings["allocator"], obfuscated_strings["Calib"]
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn anonymous_scopes() {
+        let (result, obfuscated_strings) = obfuscate(
+            "Holder<(anonymous namespace)::Calib, (anonymous struct at calib.c:12:3)>",
+            (false, false),
+        );
+        assert_eq!(
+            result,
+            format!(
+                "{}<(anonymous namespace)::{}, (anonymous struct)>",
+                obfuscated_strings["Holder"], obfuscated_strings["Calib"]
+            )
+        );
+    }
+
+    #[test]
+    fn identifiers_of_std_entities() {
+        let identifiers = std_identifiers("std::array<Calib, 16>::size_type");
+        assert_eq!(identifiers, HashSet::from(["std", "array", "size_type"]));
+        let identifiers = std_identifiers("apply(std::vector<int, std::allocator<int> >&)");
+        assert_eq!(identifiers, HashSet::from(["std", "vector", "allocator"]));
+    }
+}
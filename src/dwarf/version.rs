@@ -0,0 +1,239 @@
+use super::SliceType;
+use crate::options::Options;
+use gimli::write::{Address, AttributeValue, Expression};
+use gimli::{DwAt, DwLang, Endianity, Format, Reader, Section, constants};
+
+/// the encoding of an output unit: the DWARF version and address size of the input unit,
+/// unless other values were requested on the command line
+pub(super) fn output_encoding(encoding: gimli::Encoding, options: &Options) -> gimli::Encoding {
+    let version = options.dwarf_version.unwrap_or(encoding.version);
+    gimli::Encoding {
+        version,
+        address_size: options.address_size.unwrap_or(encoding.address_size),
+        // the 64-bit format was introduced in DWARF 3
+        format: if version < 3 {
+            Format::Dwarf32
+        } else {
+            encoding.format
+        },
+    }
+}
+
+// the DWARF version that introduced an attribute. Vendor extensions are not tied to a version
+fn first_version(attrtype: DwAt) -> u16 {
+    match attrtype.0 {
+        0..=0x4d => 2,
+        0x4e..=0x68 => 3,
+        0x69..=0x6e => 4,
+        0x6f..0x2000 => 5,
+        _ => 2,
+    }
+}
+
+/// convert an attribute of the input into an attribute that the encoding of the output unit supports.
+/// Forms that only differ in their encoding (exprloc / block, flag_present / flag) are chosen by gimli.
+/// Returns None if there is no equivalent attribute, e.g. for DWARF 5 attributes in a DWARF 2 unit
+pub(super) fn convert_attribute(
+    encoding: gimli::Encoding,
+    big_endian: bool,
+    attrtype: DwAt,
+    value: AttributeValue,
+) -> Option<(DwAt, AttributeValue)> {
+    let value = match value {
+        // an address that does not fit into the address size of the output can't be represented
+        AttributeValue::Address(Address::Constant(address))
+            if encoding.address_size < 8 && address >> (encoding.address_size * 8) != 0 =>
+        {
+            return None;
+        }
+        // DW_FORM_data16 was introduced in DWARF 5; a block contains the value in target byte order
+        AttributeValue::Data16(value) if encoding.version < 5 => {
+            let bytes = if big_endian {
+                value.to_be_bytes()
+            } else {
+                value.to_le_bytes()
+            };
+            AttributeValue::Block(bytes.to_vec())
+        }
+        // before DWARF 4, the member offset had to be a location description
+        AttributeValue::Data1(_)
+        | AttributeValue::Data2(_)
+        | AttributeValue::Data4(_)
+        | AttributeValue::Data8(_)
+        | AttributeValue::Udata(_)
+            if encoding.version < 4 && attrtype == constants::DW_AT_data_member_location =>
+        {
+            member_location(constant_value(&value)?)
+        }
+        AttributeValue::Language(language) => {
+            AttributeValue::Language(older_language(language, encoding.version))
+        }
+        value => value,
+    };
+    let attrtype = if attrtype == constants::DW_AT_linkage_name && encoding.version < 4 {
+        // DWARF 2 and 3 consumers know the linkage name by its vendor attribute
+        constants::DW_AT_MIPS_linkage_name
+    } else {
+        attrtype
+    };
+
+    (first_version(attrtype) <= encoding.version).then_some((attrtype, value))
+}
+
+fn constant_value(value: &AttributeValue) -> Option<u64> {
+    match *value {
+        AttributeValue::Data1(val) => Some(u64::from(val)),
+        AttributeValue::Data2(val) => Some(u64::from(val)),
+        AttributeValue::Data4(val) => Some(u64::from(val)),
+        AttributeValue::Data8(val) | AttributeValue::Udata(val) => Some(val),
+        _ => None,
+    }
+}
+
+// the location description DW_OP_plus_uconst <offset>, which adds the offset to the address of the struct
+fn member_location(offset: u64) -> AttributeValue {
+    let mut expression = Expression::new();
+    expression.op_plus_uconst(offset);
+    AttributeValue::Exprloc(expression)
+}
+
+/// get the attributes that replace an attribute of the input entry, if its meaning changed between
+/// the DWARF versions. Returns None if the attribute can be converted by itself with convert_attribute
+pub(super) fn replaced_attributes(
+    input_dwarf: &gimli::Dwarf<SliceType>,
+    input_unit: &gimli::UnitHeader<SliceType>,
+    in_abbrevs: &gimli::Abbreviations,
+    entry: &gimli::DebuggingInformationEntry<SliceType>,
+    attrtype: DwAt,
+    encoding: gimli::Encoding,
+) -> Option<Vec<(DwAt, AttributeValue)>> {
+    if encoding.version >= 4 {
+        return None;
+    }
+    if attrtype == constants::DW_AT_data_bit_offset {
+        let attributes = bit_offset_attributes(input_dwarf, input_unit, in_abbrevs, entry);
+        Some(attributes.into_iter().flatten().collect())
+    } else if attrtype == constants::DW_AT_high_pc {
+        let high_pc = high_pc_address(entry).and_then(|value| {
+            convert_attribute(encoding, is_big_endian(input_dwarf), attrtype, value)
+        });
+        Some(high_pc.into_iter().collect())
+    } else {
+        None
+    }
+}
+
+// convert the DW_AT_data_bit_offset of a bitfield member (DWARF 4+) into the DWARF 2 / 3 attributes:
+// DW_AT_data_member_location and DW_AT_byte_size describe the storage unit that contains the bitfield,
+// and DW_AT_bit_offset counts the bits from the most significant bit of the storage unit
+fn bit_offset_attributes(
+    input_dwarf: &gimli::Dwarf<SliceType>,
+    input_unit: &gimli::UnitHeader<SliceType>,
+    in_abbrevs: &gimli::Abbreviations,
+    entry: &gimli::DebuggingInformationEntry<SliceType>,
+) -> Option<[(DwAt, AttributeValue); 3]> {
+    let data_bit_offset = entry
+        .attr_value(constants::DW_AT_data_bit_offset)?
+        .udata_value()?;
+    let bit_size = entry.attr_value(constants::DW_AT_bit_size)?.udata_value()?;
+    let byte_size = match entry.attr_value(constants::DW_AT_byte_size) {
+        Some(value) => value.udata_value()?,
+        None => type_byte_size(input_unit, in_abbrevs, entry)?,
+    };
+    let storage_bits = byte_size * 8;
+    if bit_size == 0 || bit_size > storage_bits {
+        return None;
+    }
+
+    // the storage unit is aligned to its size, unless the bitfield crosses this boundary in a packed struct
+    let mut byte_offset = data_bit_offset / storage_bits * byte_size;
+    if data_bit_offset - byte_offset * 8 + bit_size > storage_bits {
+        byte_offset = data_bit_offset / 8;
+        if data_bit_offset % 8 + bit_size > storage_bits {
+            return None;
+        }
+    }
+    let offset_in_storage = data_bit_offset - byte_offset * 8;
+    let bit_offset = if is_big_endian(input_dwarf) {
+        offset_in_storage
+    } else {
+        storage_bits - bit_size - offset_in_storage
+    };
+
+    Some([
+        (
+            constants::DW_AT_data_member_location,
+            member_location(byte_offset),
+        ),
+        (constants::DW_AT_byte_size, AttributeValue::Udata(byte_size)),
+        (
+            constants::DW_AT_bit_offset,
+            AttributeValue::Udata(bit_offset),
+        ),
+    ])
+}
+
+// before DWARF 4, DW_AT_high_pc was always an address. Since then it is usually the size of the range
+fn high_pc_address(entry: &gimli::DebuggingInformationEntry<SliceType>) -> Option<AttributeValue> {
+    let high_pc = entry.attr_value(constants::DW_AT_high_pc)?;
+    let address = match high_pc {
+        gimli::AttributeValue::Addr(address) => address,
+        _ => {
+            let gimli::AttributeValue::Addr(low_pc) = entry.attr_value(constants::DW_AT_low_pc)?
+            else {
+                return None;
+            };
+            low_pc.checked_add(high_pc.udata_value()?)?
+        }
+    };
+    Some(AttributeValue::Address(Address::Constant(address)))
+}
+
+// language codes that were added in later DWARF versions are replaced by the closest older code
+fn older_language(language: DwLang, version: u16) -> DwLang {
+    let language = match language {
+        constants::DW_LANG_C11 | constants::DW_LANG_C17 if version < 5 => constants::DW_LANG_C99,
+        constants::DW_LANG_C_plus_plus_11
+        | constants::DW_LANG_C_plus_plus_14
+        | constants::DW_LANG_C_plus_plus_17
+        | constants::DW_LANG_C_plus_plus_20
+            if version < 5 =>
+        {
+            constants::DW_LANG_C_plus_plus
+        }
+        constants::DW_LANG_Fortran03 | constants::DW_LANG_Fortran08 if version < 5 => {
+            constants::DW_LANG_Fortran95
+        }
+        language => language,
+    };
+    match language {
+        constants::DW_LANG_C99 if version < 3 => constants::DW_LANG_C89,
+        constants::DW_LANG_Fortran95 if version < 3 => constants::DW_LANG_Fortran90,
+        language => language,
+    }
+}
+
+pub(super) fn is_big_endian(input_dwarf: &gimli::Dwarf<SliceType>) -> bool {
+    input_dwarf.debug_info.reader().endian().is_big_endian()
+}
+
+// get the size of the type of an entry, following typedefs and qualifiers
+fn type_byte_size(
+    input_unit: &gimli::UnitHeader<SliceType>,
+    in_abbrevs: &gimli::Abbreviations,
+    entry: &gimli::DebuggingInformationEntry<SliceType>,
+) -> Option<u64> {
+    let mut type_ref = entry.attr_value(constants::DW_AT_type);
+    // the depth limit protects against reference loops in broken input files
+    for _ in 0..16 {
+        let Some(gimli::AttributeValue::UnitRef(offset)) = type_ref else {
+            return None;
+        };
+        let type_entry = input_unit.entry(in_abbrevs, offset).ok()?;
+        if let Some(size) = type_entry.attr_value(constants::DW_AT_byte_size) {
+            return size.udata_value();
+        }
+        type_ref = type_entry.attr_value(constants::DW_AT_type);
+    }
+    None
+}
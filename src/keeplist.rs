@@ -0,0 +1,361 @@
+use regex::Regex;
+use std::collections::HashSet;
+use std::ffi::OsStr;
+
+// names of C / C++ base types, standard typedefs and macros, and AUTOSAR platform types and macros,
+// which are kept unless --no-default-keep is given
+static DEFAULT_KEEP_LIST: &[&str] = &[
+    // C / C++ base types, as they are named by gcc and clang
+    "void",
+    "_Bool",
+    "bool",
+    "char",
+    "signed char",
+    "unsigned char",
+    "char8_t",
+    "char16_t",
+    "char32_t",
+    "wchar_t",
+    "short",
+    "short int",
+    "short unsigned int",
+    "unsigned short",
+    "int",
+    "unsigned int",
+    "long",
+    "long int",
+    "long unsigned int",
+    "unsigned long",
+    "long long",
+    "long long int",
+    "long long unsigned int",
+    "unsigned long long",
+    "__int128",
+    "__int128 unsigned",
+    "float",
+    "double",
+    "long double",
+    "sizetype",
+    "decltype(nullptr)",
+    // stdint.h / stddef.h, and the glibc types they are defined with
+    "int8_t",
+    "int16_t",
+    "int32_t",
+    "int64_t",
+    "uint8_t",
+    "uint16_t",
+    "uint32_t",
+    "uint64_t",
+    "__int8_t",
+    "__int16_t",
+    "__int32_t",
+    "__int64_t",
+    "__uint8_t",
+    "__uint16_t",
+    "__uint32_t",
+    "__uint64_t",
+    "int_least8_t",
+    "int_least16_t",
+    "int_least32_t",
+    "int_least64_t",
+    "uint_least8_t",
+    "uint_least16_t",
+    "uint_least32_t",
+    "uint_least64_t",
+    "__int_least8_t",
+    "__int_least16_t",
+    "__int_least32_t",
+    "__int_least64_t",
+    "__uint_least8_t",
+    "__uint_least16_t",
+    "__uint_least32_t",
+    "__uint_least64_t",
+    "int_fast8_t",
+    "int_fast16_t",
+    "int_fast32_t",
+    "int_fast64_t",
+    "uint_fast8_t",
+    "uint_fast16_t",
+    "uint_fast32_t",
+    "uint_fast64_t",
+    "intptr_t",
+    "uintptr_t",
+    "__intptr_t",
+    "intmax_t",
+    "uintmax_t",
+    "__intmax_t",
+    "__uintmax_t",
+    "size_t",
+    "ssize_t",
+    "ptrdiff_t",
+    "wint_t",
+    // AUTOSAR platform types and standard types
+    "boolean",
+    "sint8",
+    "sint16",
+    "sint32",
+    "sint64",
+    "uint8",
+    "uint16",
+    "uint32",
+    "uint64",
+    "sint8_least",
+    "sint16_least",
+    "sint32_least",
+    "uint8_least",
+    "uint16_least",
+    "uint32_least",
+    "float32",
+    "float64",
+    "Std_ReturnType",
+    "Std_VersionInfoType",
+    "StatusType",
+    // standard macros: stddef.h, limits.h, stdint.h, float.h, stdbool.h and assert.h
+    "NULL",
+    "offsetof",
+    "CHAR_BIT",
+    "SCHAR_MIN",
+    "SCHAR_MAX",
+    "UCHAR_MAX",
+    "CHAR_MIN",
+    "CHAR_MAX",
+    "SHRT_MIN",
+    "SHRT_MAX",
+    "USHRT_MAX",
+    "INT_MIN",
+    "INT_MAX",
+    "UINT_MAX",
+    "LONG_MIN",
+    "LONG_MAX",
+    "ULONG_MAX",
+    "LLONG_MIN",
+    "LLONG_MAX",
+    "ULLONG_MAX",
+    "INT8_MIN",
+    "INT8_MAX",
+    "UINT8_MAX",
+    "INT16_MIN",
+    "INT16_MAX",
+    "UINT16_MAX",
+    "INT32_MIN",
+    "INT32_MAX",
+    "UINT32_MAX",
+    "INT64_MIN",
+    "INT64_MAX",
+    "UINT64_MAX",
+    "INT8_C",
+    "INT16_C",
+    "INT32_C",
+    "INT64_C",
+    "UINT8_C",
+    "UINT16_C",
+    "UINT32_C",
+    "UINT64_C",
+    "INTMAX_C",
+    "UINTMAX_C",
+    "INTPTR_MIN",
+    "INTPTR_MAX",
+    "UINTPTR_MAX",
+    "INTMAX_MIN",
+    "INTMAX_MAX",
+    "UINTMAX_MAX",
+    "PTRDIFF_MIN",
+    "PTRDIFF_MAX",
+    "SIZE_MAX",
+    "FLT_MIN",
+    "FLT_MAX",
+    "FLT_EPSILON",
+    "DBL_MIN",
+    "DBL_MAX",
+    "DBL_EPSILON",
+    "true",
+    "false",
+    "assert",
+    "static_assert",
+    // AUTOSAR standard macros and compiler abstraction
+    "TRUE",
+    "FALSE",
+    "STD_ON",
+    "STD_OFF",
+    "STD_HIGH",
+    "STD_LOW",
+    "STD_ACTIVE",
+    "STD_IDLE",
+    "E_OK",
+    "E_NOT_OK",
+    "NULL_PTR",
+    "FUNC",
+    "FUNC_P2CONST",
+    "FUNC_P2VAR",
+    "P2VAR",
+    "P2CONST",
+    "CONSTP2VAR",
+    "CONSTP2CONST",
+    "P2FUNC",
+    "CONST",
+    "VAR",
+    "AUTOMATIC",
+    "TYPEDEF",
+    "STATIC",
+    "INLINE",
+    "LOCAL_INLINE",
+];
+
+/// names that are never obfuscated: exact names, glob patterns using '*' and '?',
+/// and regular expressions with the prefix "re:", which must match the whole name
+#[derive(Debug, Default)]
+pub(crate) struct KeepList {
+    names: HashSet<String>,
+    patterns: Vec<String>,
+    regexes: Vec<Regex>,
+}
+
+impl KeepList {
+    /// create a keep list, optionally filled with the default names
+    pub(crate) fn new(use_defaults: bool) -> Self {
+        let mut keep_list = KeepList::default();
+        if use_defaults {
+            for entry in DEFAULT_KEEP_LIST {
+                keep_list.names.insert(entry.to_string());
+            }
+        }
+        keep_list
+    }
+
+    /// add a name, a glob pattern or a regular expression to the list
+    pub(crate) fn add(&mut self, entry: &str) -> Result<(), String> {
+        if let Some(expression) = entry.strip_prefix("re:") {
+            let regex = Regex::new(&format!("^(?:{expression})$"))
+                .map_err(|err| format!("invalid regular expression {expression}: {err}"))?;
+            self.regexes.push(regex);
+        } else if entry.contains(['*', '?']) {
+            self.patterns.push(entry.to_string());
+        } else {
+            self.names.insert(entry.to_string());
+        }
+        Ok(())
+    }
+
+    /// load names and patterns from a text file with one entry per line.
+    /// Empty lines and lines starting with '#' are ignored
+    pub(crate) fn load_file(&mut self, filename: &OsStr) -> Result<(), String> {
+        let text = std::fs::read_to_string(filename).map_err(|err| {
+            format!(
+                "failed to read keep list {}: {err}",
+                filename.to_string_lossy()
+            )
+        })?;
+        for line in text.lines() {
+            let line = line.trim();
+            if !line.is_empty() && !line.starts_with('#') {
+                self.add(line)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// check if a name must be kept
+    pub(crate) fn contains(&self, name: &str) -> bool {
+        self.names.contains(name)
+            || self
+                .patterns
+                .iter()
+                .any(|pattern| glob_match(pattern.as_bytes(), name.as_bytes()))
+            || self.regexes.iter().any(|regex| regex.is_match(name))
+    }
+}
+
+// match a name against a glob pattern: '*' matches any sequence of characters, '?' matches one character
+fn glob_match(pattern: &[u8], name: &[u8]) -> bool {
+    let mut pat_pos = 0;
+    let mut name_pos = 0;
+    // position of the last '*' in the pattern, and the position in the name where it started matching
+    let mut backtrack = None;
+    while name_pos < name.len() {
+        if pat_pos < pattern.len()
+            && (pattern[pat_pos] == b'?' || pattern[pat_pos] == name[name_pos])
+        {
+            pat_pos += 1;
+            name_pos += 1;
+        } else if pat_pos < pattern.len() && pattern[pat_pos] == b'*' {
+            backtrack = Some((pat_pos, name_pos));
+            pat_pos += 1;
+        } else if let Some((star_pos, star_name_pos)) = backtrack {
+            // let the '*' match one more character
+            pat_pos = star_pos + 1;
+            name_pos = star_name_pos + 1;
+            backtrack = Some((star_pos, name_pos));
+        } else {
+            return false;
+        }
+    }
+    pattern[pat_pos..].iter().all(|c| *c == b'*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob_match_literal() {
+        assert!(glob_match(b"uint8_t", b"uint8_t"));
+        assert!(!glob_match(b"uint8_t", b"uint8_t2"));
+        assert!(!glob_match(b"uint8_t", b"uint8"));
+        assert!(glob_match(b"", b""));
+        assert!(!glob_match(b"", b"a"));
+    }
+
+    #[test]
+    fn glob_match_wildcards() {
+        assert!(glob_match(b"*", b""));
+        assert!(glob_match(b"*", b"anything"));
+        assert!(glob_match(b"Rte_*", b"Rte_Read"));
+        assert!(!glob_match(b"Rte_*", b"Rt_Read"));
+        assert!(glob_match(b"*_t", b"config_t"));
+        assert!(glob_match(b"?int8_t", b"uint8_t"));
+        assert!(!glob_match(b"?int8_t", b"int8_t"));
+        assert!(glob_match(b"a**b", b"ab"));
+        assert!(glob_match(b"a*b*", b"axxbyy"));
+    }
+
+    #[test]
+    fn glob_match_backtracking() {
+        // the first '*' must give back characters that it matched earlier
+        assert!(glob_match(b"*ab", b"aab"));
+        assert!(glob_match(b"*abc", b"ababc"));
+        assert!(glob_match(b"a*b*c", b"abbbbc"));
+        assert!(glob_match(b"*_t*_t", b"x_t_y_t"));
+        assert!(!glob_match(b"*ab", b"aba"));
+        assert!(!glob_match(b"a*b?c", b"abbc_"));
+        assert!(glob_match(b"a*?", b"ab"));
+        assert!(!glob_match(b"a*?", b"a"));
+    }
+
+    #[test]
+    fn default_keep_list() {
+        let keep_list = KeepList::new(true);
+        assert!(keep_list.contains("uint8_t"));
+        assert!(keep_list.contains("__int32_t"));
+        assert!(keep_list.contains("uint_fast16_t"));
+        // user typedefs that only look similar to the standard types are obfuscated
+        assert!(!keep_list.contains("internal_state_t"));
+        assert!(!keep_list.contains("uint_config_t"));
+        assert!(!KeepList::new(false).contains("uint8_t"));
+    }
+
+    #[test]
+    fn keep_list_entries() {
+        let mut keep_list = KeepList::new(false);
+        keep_list.add("Calib").unwrap();
+        keep_list.add("Rte_*").unwrap();
+        keep_list.add("re:Dem_[A-Z][a-z]+").unwrap();
+        assert!(keep_list.contains("Calib"));
+        assert!(!keep_list.contains("Calib2"));
+        assert!(keep_list.contains("Rte_Write"));
+        assert!(keep_list.contains("Dem_Event"));
+        // the regular expression must match the whole name
+        assert!(!keep_list.contains("Dem_EventX"));
+        assert!(!keep_list.contains("xDem_Event"));
+        assert!(keep_list.add("re:(").is_err());
+    }
+}
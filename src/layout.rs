@@ -0,0 +1,181 @@
+use object::build::elf::{Builder, Section, SectionData};
+use rand::{RngExt, rngs::StdRng, seq::SliceRandom};
+
+/// the address ranges of the kept sections and segments, and the addresses they were moved to
+#[derive(Debug, Default)]
+pub(crate) struct AddressLayout {
+    // (original start address, size, new start address)
+    ranges: Vec<(u64, u64, u64)>,
+}
+
+impl AddressLayout {
+    /// get the new address for an address inside of one of the moved ranges
+    pub(crate) fn translate(&self, address: u64) -> Option<u64> {
+        self.ranges
+            .iter()
+            .find(|(start, size, _)| address >= *start && address - *start < *size)
+            .map(|(start, _, new_start)| new_start + (address - start))
+    }
+}
+
+// allocated sections that are used by the loader or the runtime, and never contain variables
+const NON_DATA_SECTIONS: [&str; 5] = [".interp", ".got", ".eh_frame", ".gcc_except_table", ".note"];
+
+/// check if a section holds variables: data, bss and calibration sections are allocated,
+/// but not executable. Thread local data is excluded, because its addresses are offsets
+pub(crate) fn is_placeholder_section(sec: &Section<'_>) -> bool {
+    let excluded_flags = u64::from(object::elf::SHF_EXECINSTR | object::elf::SHF_TLS);
+    sec.is_alloc()
+        && sec.sh_flags & excluded_flags == 0
+        && sec.sh_size > 0
+        && matches!(
+            sec.sh_type,
+            object::elf::SHT_PROGBITS | object::elf::SHT_NOBITS
+        )
+        && !NON_DATA_SECTIONS
+            .iter()
+            .any(|name| sec.name.starts_with(name.as_bytes()))
+}
+
+/// turn a section into a SHT_NOBITS placeholder, which keeps its address and size, but not its content
+pub(crate) fn make_placeholder(sec: &mut Section<'_>) {
+    sec.sh_type = object::elf::SHT_NOBITS;
+    sec.sh_offset = 0;
+    sec.data = SectionData::UninitializedData(sec.sh_size);
+}
+
+/// keep the program headers of the loadable segments. The other segments (dynamic linking, notes,
+/// thread local data, ...) are removed, and the loadable segments no longer contain any file data.
+/// Segments that don't contain any placeholder section are removed later with the orphans
+pub(crate) fn keep_load_segments(elf_builder: &mut Builder<'_>) {
+    for seg in &mut elf_builder.segments {
+        if seg.is_load() {
+            seg.p_offset = 0;
+            seg.p_filesz = 0;
+        } else {
+            // segments without sections are not revived by delete_orphans
+            seg.delete = true;
+            seg.sections.clear();
+        }
+    }
+    // the program headers are written directly after the file header
+    elf_builder.header.e_phoff = if elf_builder.is_64 { 64 } else { 52 };
+}
+
+/// move the kept segments, and the kept sections that are not part of a segment, to random addresses.
+/// Each segment is moved as a whole, so the sections inside of it keep their relative positions,
+/// and the alignment of all addresses is preserved
+pub(crate) fn randomize_layout(elf_builder: &mut Builder<'_>, rng: &mut StdRng) -> AddressLayout {
+    // (start, size, alignment) of each block that is moved as a whole
+    let mut blocks = vec![];
+    for seg in &elf_builder.segments {
+        if !seg.delete {
+            blocks.push((seg.p_vaddr, seg.p_memsz, seg.p_align));
+        }
+    }
+    for sec in &elf_builder.sections {
+        if !sec.delete && sec.is_alloc() && !in_segment(elf_builder, sec) {
+            blocks.push((sec.sh_addr, sec.sh_size, sec.sh_addralign));
+        }
+    }
+    blocks.shuffle(rng);
+
+    // the blocks are placed one after the other, with random gaps, starting at a random address
+    let mut layout = AddressLayout::default();
+    let mut next_address = rng.random_range(0..0x8000_0000u64);
+    for (start, size, align) in blocks {
+        // the lower 8 bits of the addresses are always kept, like for variables outside of any section
+        let align = align.max(0x100).next_power_of_two();
+        let new_start = (next_address & !(align - 1)) + align + (start & (align - 1));
+        layout.ranges.push((start, size, new_start));
+        next_address = new_start + size + rng.random_range(0..0x10_0000u64);
+    }
+
+    for seg in &mut elf_builder.segments {
+        if let Some(new_start) = layout.translate(seg.p_vaddr) {
+            seg.p_paddr = seg
+                .p_paddr
+                .wrapping_add(new_start.wrapping_sub(seg.p_vaddr));
+            seg.p_vaddr = new_start;
+        }
+    }
+    for sec in &mut elf_builder.sections {
+        if !sec.delete
+            && sec.is_alloc()
+            && let Some(new_start) = layout.translate(sec.sh_addr)
+        {
+            sec.sh_addr = new_start;
+        }
+    }
+
+    layout
+}
+
+fn in_segment(elf_builder: &Builder<'_>, sec: &Section<'_>) -> bool {
+    elf_builder
+        .segments
+        .iter()
+        .any(|seg| !seg.delete && seg.sections.contains(&sec.id()))
+}
+
+/// assign file offsets to the kept sections that have content, e.g. calibration data.
+/// Each segment that contains such sections is shortened to start at its first section with
+/// content, so that the file does not need to contain the removed code in front of it
+pub(crate) fn place_section_data(elf_builder: &mut Builder<'_>) {
+    if elf_builder.segments.is_empty() {
+        // without program headers, the builder chooses the file offsets itself
+        return;
+    }
+    let (header_size, program_header_size) = if elf_builder.is_64 {
+        (64, 56)
+    } else {
+        (52, 32)
+    };
+    let mut offset = header_size + program_header_size * elf_builder.segments.count() as u64;
+
+    let has_content = |sec: &Section<'_>| sec.is_alloc() && sec.sh_type != object::elf::SHT_NOBITS;
+    let mut placed = vec![];
+    for seg in &mut elf_builder.segments {
+        let sections: Vec<_> = seg
+            .sections
+            .iter()
+            .map(|id| elf_builder.sections.get(*id))
+            .filter(|sec| has_content(sec))
+            .map(|sec| (sec.id(), sec.sh_addr, sec.sh_size))
+            .collect();
+        let Some(start) = sections.iter().map(|(_, addr, _)| *addr).min() else {
+            continue;
+        };
+        let end = sections
+            .iter()
+            .map(|(_, addr, size)| addr + size)
+            .max()
+            .unwrap_or(start);
+
+        // the file offset and the address of a segment must be equal modulo the alignment
+        let align = seg.p_align.max(1);
+        offset = offset.next_multiple_of(align) + start % align;
+        let skipped = start - seg.p_vaddr;
+        seg.p_vaddr = start;
+        seg.p_paddr = seg.p_paddr.wrapping_add(skipped);
+        seg.p_memsz -= skipped;
+        seg.p_offset = offset;
+        seg.p_filesz = end - start;
+        for (id, addr, _) in sections {
+            placed.push((id, offset + (addr - start)));
+        }
+        offset += end - start;
+    }
+    for (id, sh_offset) in &placed {
+        elf_builder.sections.get_mut(*id).sh_offset = *sh_offset;
+    }
+
+    // sections with content outside of all segments are placed after the segments
+    for sec in &mut elf_builder.sections {
+        if has_content(sec) && !placed.iter().any(|(id, _)| *id == sec.id()) {
+            offset = offset.next_multiple_of(sec.sh_addralign.max(1));
+            sec.sh_offset = offset;
+            offset += sec.sh_size;
+        }
+    }
+}
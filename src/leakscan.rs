@@ -0,0 +1,288 @@
+use crate::keeplist::KeepList;
+use crate::options::Options;
+use object::{Object, ObjectSection};
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+/// search the output elf and a2l files for identifiers of the input that should have been obfuscated.
+/// The identifiers are the original names in the string mapping of the debug info and the names
+/// of the renamed a2l objects; identifiers shorter than options.leak_min_length are ignored.
+/// Returns one description for each identifier and location
+pub(crate) fn scan_leaks(
+    options: &Options,
+    dwarf_stringmapping: &HashMap<String, String>,
+) -> Result<Vec<String>, String> {
+    let a2l_names = crate::a2l::renamed_object_names(&options.a2l_in, &options.keep_list)?;
+    let identifiers = original_identifiers(
+        dwarf_stringmapping,
+        &a2l_names,
+        &options.keep_list,
+        options.leak_min_length,
+    );
+
+    let mut leaks = scan_elf(&options.elf_out, &identifiers)?;
+
+    let a2l_text = std::fs::read(&options.a2l_out)
+        .map_err(|e| format!("could not read {}: {e}", options.a2l_out.to_string_lossy()))?;
+    for (line_idx, line) in a2l_text.split(|c| *c == b'\n').enumerate() {
+        // "begin" in the a2l keyword /begin is not a leak, e.g. of the obfuscated method vector::begin
+        let mut line_identifiers: Vec<_> = find_identifiers(line, &identifiers)
+            .into_iter()
+            .filter(|(offset, identifier)| {
+                identifier != "begin" || *offset == 0 || line[offset - 1] != b'/'
+            })
+            .map(|(_, identifier)| identifier)
+            .collect();
+        line_identifiers.sort();
+        line_identifiers.dedup();
+        for identifier in line_identifiers {
+            leaks.push(format!("{identifier}: a2l file, line {}", line_idx + 1));
+        }
+    }
+
+    Ok(leaks)
+}
+
+// collect the identifiers inside of the original names that were replaced by other names.
+// Names that occur in the output on purpose (kept names) are excluded
+fn original_identifiers(
+    dwarf_stringmapping: &HashMap<String, String>,
+    a2l_names: &[String],
+    keep_list: &KeepList,
+    min_length: usize,
+) -> HashSet<Vec<u8>> {
+    let kept: HashSet<&str> = dwarf_stringmapping
+        .iter()
+        .filter(|(original, obfuscated)| original == obfuscated)
+        .map(|(original, _)| original.as_str())
+        .collect();
+    // the mapping also contains qualified keys like "name{CompileUnit:unit_c}" for renamed variables
+    let names = dwarf_stringmapping
+        .iter()
+        .filter(|(original, obfuscated)| original != obfuscated)
+        .map(|(original, _)| original.replace("{CompileUnit:", "{"))
+        .chain(a2l_names.iter().cloned());
+
+    let mut identifiers = HashSet::new();
+    for name in names {
+        for (_, token) in identifier_tokens(name.as_bytes()) {
+            let Ok(token_str) = std::str::from_utf8(token) else {
+                continue;
+            };
+            if token.len() >= min_length
+                && !token[0].is_ascii_digit()
+                && !kept.contains(token_str)
+                && !keep_list.contains(token_str)
+            {
+                identifiers.insert(token.to_vec());
+            }
+        }
+    }
+    identifiers
+}
+
+// search the output elf file. The contents of the sections are decompressed before the search,
+// since the debug sections may be compressed; the bytes outside of the sections are searched as they are
+fn scan_elf(
+    filename: &std::ffi::OsStr,
+    identifiers: &HashSet<Vec<u8>>,
+) -> Result<Vec<String>, String> {
+    let filedata = crate::load_filedata(filename)?;
+    let elffile = object::File::parse(&*filedata).map_err(|e| e.to_string())?;
+
+    // (identifier, section name) -> (offset of the first occurrence, number of occurrences)
+    let mut found = BTreeMap::<(String, String), (u64, usize)>::new();
+    let mut add_occurrences = |data: &[u8], base_offset: u64, section_name: &str| {
+        for (offset, identifier) in find_identifiers(data, identifiers) {
+            let entry = found
+                .entry((identifier, section_name.to_string()))
+                .or_insert((base_offset + offset as u64, 0));
+            entry.1 += 1;
+        }
+    };
+
+    let mut section_ranges = vec![];
+    for section in elffile.sections() {
+        let Some((offset, size)) = section.file_range() else {
+            continue;
+        };
+        section_ranges.push((offset, offset + size));
+        let name = section.name().unwrap_or("<unnamed>");
+        let data = section
+            .uncompressed_data()
+            .map_err(|e| format!("could not read section {name}: {e}"))?;
+        // offsets inside of a section are relative to the start of its uncompressed data
+        add_occurrences(&data, 0, name);
+    }
+
+    // the elf header, the program headers, the section headers and any padding between the sections
+    section_ranges.sort_unstable();
+    let mut pos = 0;
+    for (start, end) in section_ranges
+        .into_iter()
+        .chain([(filedata.len() as u64, 0)])
+    {
+        let start = start.min(filedata.len() as u64);
+        if pos < start {
+            add_occurrences(
+                &filedata[pos as usize..start as usize],
+                pos,
+                "<outside of sections>",
+            );
+        }
+        pos = pos.max(end);
+    }
+
+    Ok(found
+        .into_iter()
+        .map(|((identifier, section_name), (offset, count))| {
+            format!(
+                "{identifier}: elf file, section {section_name} at offset {offset:#x}, occurrences: {count}"
+            )
+        })
+        .collect())
+}
+
+// find the identifiers that occur as a whole word in the data, or as a component of a
+// mangled C++ name, e.g. "calib" in "_ZN6engine5calibE"
+fn find_identifiers(data: &[u8], identifiers: &HashSet<Vec<u8>>) -> Vec<(usize, String)> {
+    let mut result = vec![];
+    for (offset, token) in identifier_tokens(data) {
+        if identifiers.contains(token) {
+            result.push((offset, String::from_utf8_lossy(token).into_owned()));
+        } else if token.starts_with(b"_Z") {
+            for (component_offset, component) in mangled_components(token) {
+                if identifiers.contains(component) {
+                    result.push((
+                        offset + component_offset,
+                        String::from_utf8_lossy(component).into_owned(),
+                    ));
+                }
+            }
+        }
+    }
+    result
+}
+
+// split the data into runs of the characters that can occur in identifiers
+fn identifier_tokens(data: &[u8]) -> impl Iterator<Item = (usize, &[u8])> {
+    let is_identifier_char = |c: &u8| c.is_ascii_alphanumeric() || *c == b'_' || *c == b'$';
+    let mut pos = 0;
+    std::iter::from_fn(move || {
+        let start = pos + data[pos..].iter().position(is_identifier_char)?;
+        let len = data[start..]
+            .iter()
+            .position(|c| !is_identifier_char(c))
+            .unwrap_or(data.len() - start);
+        pos = start + len;
+        Some((start, &data[start..pos]))
+    })
+}
+
+// the names in a mangled name are encoded as <length><name>
+fn mangled_components(mangled: &[u8]) -> Vec<(usize, &[u8])> {
+    let mut components = vec![];
+    let mut pos = 2;
+    while pos < mangled.len() {
+        // a component can end with a digit, so the length of the next one may follow a digit
+        if mangled[pos].is_ascii_digit() {
+            let digits = mangled[pos..]
+                .iter()
+                .take_while(|c| c.is_ascii_digit())
+                .count();
+            let length = std::str::from_utf8(&mangled[pos..pos + digits])
+                .ok()
+                .and_then(|len| len.parse::<usize>().ok())
+                .unwrap_or(0);
+            let start = pos + digits;
+            if length > 0
+                && let Some(component) = start
+                    .checked_add(length)
+                    .and_then(|end| mangled.get(start..end))
+            {
+                components.push((start, component));
+                pos = start + length;
+                continue;
+            }
+            pos = start;
+        } else {
+            pos += 1;
+        }
+    }
+    components
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identifier_set(names: &[&str]) -> HashSet<Vec<u8>> {
+        names.iter().map(|name| name.as_bytes().to_vec()).collect()
+    }
+
+    #[test]
+    fn mangled_name_components() {
+        assert_eq!(
+            mangled_components(b"_ZN6engine5calibE"),
+            [(4, &b"engine"[..]), (11, &b"calib"[..])]
+        );
+        // the length of a component is not confused with the digits inside of a name
+        assert_eq!(
+            mangled_components(b"_ZN4can25speedE"),
+            [(4, &b"can2"[..]), (9, &b"speed"[..])]
+        );
+        // a malformed length
+        assert_eq!(mangled_components(b"_Z18446744073709551615speed"), []);
+
+        let identifiers = identifier_set(&["calib"]);
+        assert_eq!(
+            find_identifiers(b"\0_ZN6engine5calibE\0", &identifiers),
+            [(12, "calib".to_string())]
+        );
+    }
+
+    #[test]
+    fn tokens_next_to_digits() {
+        // digits are part of an identifier, so "calib" is not found in "calib2" or "2calib"
+        let identifiers = identifier_set(&["calib"]);
+        assert!(find_identifiers(b"calib2 2calib _calib", &identifiers).is_empty());
+        assert_eq!(
+            find_identifiers(b"12.calib+3", &identifiers),
+            [(3, "calib".to_string())]
+        );
+
+        let tokens: Vec<_> = identifier_tokens(b"a1 (b_2)$c").collect();
+        assert_eq!(tokens, [(0, &b"a1"[..]), (4, &b"b_2"[..]), (8, &b"$c"[..])]);
+    }
+
+    #[test]
+    fn compile_unit_qualifiers() {
+        let mapping = HashMap::from([(
+            "counter{CompileUnit:engine_ctrl}".to_string(),
+            "xQ7pzZ1".to_string(),
+        )]);
+        let identifiers = original_identifiers(&mapping, &[], &KeepList::new(false), 5);
+        // the unit name is an identifier of its own, not the suffix of "CompileUnit:engine_ctrl"
+        assert_eq!(identifiers, identifier_set(&["counter", "engine_ctrl"]));
+    }
+
+    #[test]
+    fn kept_names_are_excluded() {
+        let mapping = HashMap::from([
+            ("engine_speed".to_string(), "kf83Ja_mq2p".to_string()),
+            // kept names are mapped to themselves
+            ("engine".to_string(), "engine".to_string()),
+            ("Rte_Read_speed".to_string(), "Rte_Read_speed".to_string()),
+            ("uint16_t".to_string(), "bVq7x_2z".to_string()),
+        ]);
+        let mut keep_list = KeepList::new(true);
+        keep_list.add("calib_*").unwrap();
+        let a2l_names = ["calib_table".to_string(), "speed_map".to_string()];
+        let identifiers = original_identifiers(&mapping, &a2l_names, &keep_list, 5);
+        // uint16_t is on the default keep list, and calib_table matches the pattern
+        assert_eq!(identifiers, identifier_set(&["engine_speed", "speed_map"]));
+
+        // short identifiers are ignored
+        let identifiers = original_identifiers(&mapping, &a2l_names, &keep_list, 10);
+        assert_eq!(identifiers, identifier_set(&["engine_speed"]));
+    }
+}
@@ -1,70 +1,248 @@
 use gimli::{EndianSlice, RunTimeEndian};
-use memmap2;
 use object::build::elf::SectionData;
-use std::{collections::HashMap, ffi::OsStr, fs::File};
+use object::{Endian, Object, ObjectSection};
+use rand::{SeedableRng, rngs::StdRng};
+use std::{
+    collections::{HashMap, HashSet},
+    ffi::OsStr,
+    fs::File,
+    io::Write,
+    time::Instant,
+};
 
 type SliceType<'a> = EndianSlice<'a, RunTimeEndian>;
 
 mod a2l;
+mod caldata;
 mod dwarf;
+mod keeplist;
+mod layout;
+mod leakscan;
+mod options;
+mod relocate;
+mod report;
+mod splitdwarf;
+mod symtab;
 
 fn main() -> Result<(), String> {
     let args = std::env::args_os().collect::<Vec<_>>();
-    if args.len() != 5 {
-        eprintln!(
-            "Usage: {} <input ELF> <output ELF> <input A2L> <output A2L>",
-            args[0].to_string_lossy()
-        );
-        std::process::exit(1);
-    }
+    let options = match options::Options::parse(&args[1..]) {
+        Ok(options) => options,
+        Err(errmsg) => {
+            eprintln!("Error: {errmsg}");
+            eprintln!(
+                "Usage: {} <input ELF> <output ELF> <input A2L> <output A2L> [options]\n{}",
+                args[0].to_string_lossy(),
+                options::USAGE_OPTIONS
+            );
+            std::process::exit(1);
+        }
+    };
 
-    run(&args[1], &args[2], &args[3], &args[4])
+    run(&options)
 }
 
-fn run(
-    elf_filename_in: &OsStr,
-    elf_filename_out: &OsStr,
-    a2l_filename_in: &OsStr,
-    a2l_filename_out: &OsStr,
-) -> Result<(), String> {
-    let input_data = load_filedata(elf_filename_in)?;
+fn run(options: &options::Options) -> Result<(), String> {
+    let mut report = report::Report::default();
+    let start_time = Instant::now();
+    let mut phase_start = start_time;
+
+    let input_data = load_filedata(&options.elf_in)?;
 
     let mut elf_builder =
         object::build::elf::Builder::read(&*input_data).map_err(|e| e.to_string())?;
+    decompress_debug_sections(&mut elf_builder, &input_data)?;
+    let input_sections = section_names(&elf_builder);
+    let unsupported_relocations = relocate::apply_debug_relocations(&mut elf_builder, &input_data)?;
+    if unsupported_relocations > 0 {
+        report.add_warning(format!(
+            "{unsupported_relocations} relocations in the debug sections are not supported and were not applied"
+        ));
+    }
+
+    // in prune mode, find the variables that are referenced in the a2l file before the debug info is rewritten
+    let retained_variables = if options.prune {
+        Some(a2l::referenced_variables(
+            &options.a2l_in,
+            &options.elf_in,
+            &options.split_dwarf,
+            &mut report,
+        )?)
+    } else {
+        None
+    };
+
+    // the values of the calibration data are replaced, only their locations are needed
+    let calibration_objects = if options.calibration_data != options::CalibrationData::Remove {
+        a2l::calibration_objects(
+            &options.a2l_in,
+            &options.elf_in,
+            &options.split_dwarf,
+            &mut report,
+        )?
+    } else {
+        vec![]
+    };
 
-    cleanup_file(&mut elf_builder);
+    // the EPK string is located in the input file, before its section loses the content
+    let epk = if options.replace_epk {
+        a2l::read_epk(
+            &options.a2l_in,
+            &options.elf_in,
+            &options.split_dwarf,
+            &mut report,
+        )?
+    } else {
+        None
+    };
+    let epk_address = epk
+        .as_ref()
+        .and_then(|epk| caldata::find_epk(&elf_builder, &epk.identifier, epk.address));
+    if epk.is_some() && epk_address.is_none() {
+        report.add_warning(
+            "the EPK was not found in the elf file, it is only replaced in the a2l file"
+                .to_string(),
+        );
+    }
+
+    cleanup_file(&mut elf_builder, options, epk_address);
+
+    // all random reordering of the output is derived from this seed, so that it can be reproduced
+    let seed = options.seed.unwrap_or_else(rand::random);
+    if options.order == options::OutputOrder::Shuffle && options.seed.is_none() {
+        report.shuffle_seed = Some(seed);
+    }
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    // the variables inside of the kept sections move together with their section
+    let address_layout = if options.keep_layout {
+        layout::randomize_layout(&mut elf_builder, &mut rng)
+    } else {
+        layout::AddressLayout::default()
+    };
+    let new_epk = epk.map(|epk| a2l::EpkData {
+        identifier: a2l::generate_identifier(&epk.identifier, &mut rng),
+        address: epk_address.and_then(|address| address_layout.translate(address)),
+    });
+    if options.calibration_data != options::CalibrationData::Remove || epk_address.is_some() {
+        caldata::scramble_calibration_data(
+            &mut elf_builder,
+            &calibration_objects,
+            &address_layout,
+            options.calibration_data,
+            &mut rng,
+        );
+        if let Some(new_epk) = &new_epk
+            && let Some(address) = new_epk.address
+        {
+            caldata::write_epk(&mut elf_builder, address, &new_epk.identifier);
+        }
+        layout::place_section_data(&mut elf_builder);
+    }
 
-    let stringmapping = obfuscate_debug_info(&mut elf_builder)?;
+    report.add_timing("prepare", phase_start.elapsed());
+    phase_start = Instant::now();
 
-    let output = std::fs::File::create(elf_filename_out).map_err(|e| e.to_string())?;
+    let stringmapping = obfuscate_debug_info(
+        &mut elf_builder,
+        retained_variables.as_ref(),
+        &address_layout,
+        options,
+        &mut rng,
+        &mut report,
+    )?;
+    report.renamed_debug_names = stringmapping
+        .iter()
+        .filter(|(original, obfuscated)| original != obfuscated)
+        .count();
+
+    if options.symbol_table {
+        symtab::rebuild_symbol_table(&mut elf_builder, &mut report)?;
+    }
+
+    if options.compress_debug != options::DebugCompression::None {
+        compress_debug_sections(&mut elf_builder, options.compress_debug)?;
+    }
+
+    report.set_sections(input_sections, section_names(&elf_builder));
+    report.add_timing("debug info", phase_start.elapsed());
+    phase_start = Instant::now();
+
+    let output = std::fs::File::create(&options.elf_out).map_err(|e| e.to_string())?;
     let mut buffer = object::write::StreamingBuffer::new(output);
     elf_builder.write(&mut buffer).map_err(|e| e.to_string())?;
 
     a2l::obfuscate_a2l(
-        a2l_filename_in,
-        a2l_filename_out,
-        elf_filename_out,
+        options,
         &stringmapping,
+        new_epk.as_ref(),
+        &mut rng,
+        &mut report,
     )?;
+    report.add_timing("write", phase_start.elapsed());
+
+    let mut failed = false;
+    if options.scan_leaks != options::LeakScan::Off {
+        phase_start = Instant::now();
+        let leaks = leakscan::scan_leaks(options, &stringmapping)?;
+        failed |= options.scan_leaks == options::LeakScan::Fail && !leaks.is_empty();
+        report.leaks = Some(leaks);
+        report.add_timing("leak scan", phase_start.elapsed());
+    }
+
+    if options.verify {
+        phase_start = Instant::now();
+        let violations = a2l::verify_output(options, &stringmapping, &mut report)?;
+        failed |= !violations.is_empty();
+        report.violations = Some(violations);
+        report.add_timing("verify", phase_start.elapsed());
+    }
+    report.add_timing("total", start_time.elapsed());
+
+    report.print_summary();
+    if let Some(report_file) = &options.report_file {
+        report.write_json(report_file)?;
+    }
+    if failed {
+        std::process::exit(1);
+    }
 
     Ok(())
 }
 
-/// remove all sections that are not .debug_<xyz> as well as all other items that are unrelated to debug info
-fn cleanup_file(elf_builder: &mut object::build::elf::Builder<'_>) {
-    for seg in &mut elf_builder.segments {
-        seg.delete = true;
+/// remove all sections that are not .debug_<xyz> as well as all other items that are unrelated to debug info.
+/// With keep_layout, the data sections and the loadable segments are kept as empty placeholders,
+/// and the initialized data sections keep their content if the calibration data is kept.
+/// The section that contains the EPK always keeps its content
+fn cleanup_file(
+    elf_builder: &mut object::build::elf::Builder<'_>,
+    options: &options::Options,
+    epk_address: Option<u64>,
+) {
+    let keep_data = options.calibration_data != options::CalibrationData::Remove;
+    if options.keep_layout {
+        layout::keep_load_segments(elf_builder);
+    } else {
+        for seg in &mut elf_builder.segments {
+            seg.delete = true;
+        }
     }
     for sec in &mut elf_builder.sections {
         // keep only the sections that start with .debug or .shstrtab (mandatory)
-        if !sec.name.starts_with(".debug".as_bytes())
-            && !sec.name.starts_with(".shstrtab".as_bytes())
+        if sec.name.starts_with(".debug".as_bytes()) || sec.name.starts_with(".shstrtab".as_bytes())
         {
+            continue;
+        }
+        let contains_epk = epk_address.is_some_and(|address| {
+            sec.is_alloc() && address >= sec.sh_addr && address - sec.sh_addr < sec.sh_size
+        });
+        if options.keep_layout && (layout::is_placeholder_section(sec) || contains_epk) {
+            if !(contains_epk || keep_data && sec.sh_type == object::elf::SHT_PROGBITS) {
+                layout::make_placeholder(sec);
+            }
+        } else {
             sec.delete = true;
-        } else if sec.name.starts_with(".debug_macinfo".as_bytes()) {
-            // remove the .debug_macinfo section - gimli currently can't rewrite it in an obfuscated way, so delete it
-            sec.delete = true;
-        } 
+        }
     }
     for sym in &mut elf_builder.symbols {
         sym.delete = true;
@@ -83,13 +261,134 @@ fn cleanup_file(elf_builder: &mut object::build::elf::Builder<'_>) {
     elf_builder.set_section_sizes();
 }
 
+/// replace the content of compressed debug sections (SHF_COMPRESSED, or the legacy .zdebug_<xyz> sections)
+/// with the uncompressed data, so that gimli and the obfuscation see the plain debug info
+fn decompress_debug_sections<'data>(
+    elf_builder: &mut object::build::elf::Builder<'data>,
+    input_data: &'data [u8],
+) -> Result<(), String> {
+    let elffile = object::File::parse(input_data).map_err(|e| e.to_string())?;
+    // the sections of the builder are in the same order as the sections of the file.
+    // Section names are not unique in object files, e.g. .debug_macro in comdat groups
+    for (section, sec) in elffile.sections().zip(elf_builder.sections.iter_mut()) {
+        let Ok(name) = section.name_bytes() else {
+            continue;
+        };
+        if !name.starts_with(b".debug") && !name.starts_with(b".zdebug") {
+            continue;
+        }
+        let compression = section
+            .compressed_file_range()
+            .map_err(|e| e.to_string())?
+            .format;
+        if compression == object::CompressionFormat::None {
+            continue;
+        }
+        let data = section.uncompressed_data().map_err(|e| {
+            format!(
+                "failed to decompress section {}: {e}",
+                String::from_utf8_lossy(name)
+            )
+        })?;
+        if let Some(suffix) = name.strip_prefix(b".zdebug") {
+            sec.name = [b".debug", suffix].concat().into();
+        }
+        sec.sh_flags &= !u64::from(object::elf::SHF_COMPRESSED);
+        sec.sh_addralign = 1;
+        sec.data = SectionData::Data(data.into_owned().into());
+    }
+    Ok(())
+}
+
+/// compress the debug sections of the output. Each compressed section starts with an
+/// ELF compression header and has the flag SHF_COMPRESSED.
+/// Sections that would not get smaller are left uncompressed.
+fn compress_debug_sections(
+    elf_builder: &mut object::build::elf::Builder<'_>,
+    compression: options::DebugCompression,
+) -> Result<(), String> {
+    let endian = elf_builder.endian;
+    let is_64 = elf_builder.is_64;
+    for sec in &mut elf_builder.sections {
+        if sec.delete || !sec.name.starts_with(b".debug") {
+            continue;
+        }
+        let SectionData::Data(data) = &sec.data else {
+            continue;
+        };
+        let (ch_type, compressed) = match compression {
+            options::DebugCompression::None => return Ok(()),
+            options::DebugCompression::Zlib => {
+                let mut encoder =
+                    flate2::write::ZlibEncoder::new(Vec::new(), flate2::Compression::default());
+                encoder.write_all(data).map_err(|e| e.to_string())?;
+                let compressed = encoder.finish().map_err(|e| e.to_string())?;
+                (object::elf::ELFCOMPRESS_ZLIB, compressed)
+            }
+            options::DebugCompression::Zstd => {
+                let compressed = ruzstd::encoding::compress_to_vec(
+                    &**data,
+                    ruzstd::encoding::CompressionLevel::Fastest,
+                );
+                (object::elf::ELFCOMPRESS_ZSTD, compressed)
+            }
+        };
+
+        // Elf64_Chdr: type, reserved, size, alignment; Elf32_Chdr: type, size, alignment
+        let mut output = Vec::with_capacity(compressed.len() + 24);
+        output.extend(endian.write_u32(ch_type));
+        if is_64 {
+            output.extend(endian.write_u32(0));
+            output.extend(endian.write_u64(data.len() as u64));
+            output.extend(endian.write_u64(sec.sh_addralign.max(1)));
+        } else {
+            output.extend(endian.write_u32(data.len() as u32));
+            output.extend(endian.write_u32(sec.sh_addralign.max(1) as u32));
+        }
+        output.extend(compressed);
+
+        if output.len() < data.len() {
+            sec.data = SectionData::Data(output.into());
+            sec.sh_flags |= u64::from(object::elf::SHF_COMPRESSED);
+            sec.sh_addralign = if is_64 { 8 } else { 4 };
+        }
+    }
+    Ok(())
+}
+
 /// create new DWAR debug info based on the existing info - but obfuscated
 fn obfuscate_debug_info(
     elf_builder: &mut object::build::elf::Builder<'_>,
+    retained_variables: Option<&HashSet<usize>>,
+    address_layout: &layout::AddressLayout,
+    options: &options::Options,
+    rng: &mut StdRng,
+    report: &mut report::Report,
 ) -> Result<HashMap<String, String>, String> {
     let input_dwarf = load_dwarf_sections(elf_builder).map_err(|e| e.to_string())?;
-    let Some((mut output_dwarf, stringmapping)) =
-        dwarf::obfuscate_dwarf(input_dwarf).map_err(|e| e.to_string())?
+    // with -gsplit-dwarf, the elf file only contains skeleton units, the rest is in .dwo / .dwp files
+    let unsplit_dwarf =
+        splitdwarf::unsplit_dwarf(&input_dwarf, &options.elf_in, &options.split_dwarf)?;
+    let input_dwarf = match &unsplit_dwarf {
+        Some(unsplit_dwarf) => {
+            for name in &unsplit_dwarf.missing_units {
+                report.add_warning(format!(
+                    "split debug info {name} not found, only the skeleton unit is used"
+                ));
+            }
+            unsplit_dwarf.dwarf()
+        }
+        None => input_dwarf,
+    };
+    let Some((mut output_dwarf, macro_sections, stringmapping)) = dwarf::obfuscate_dwarf(
+        input_dwarf,
+        retained_variables,
+        address_layout,
+        options,
+        rng,
+        report,
+    )
+    .map_err(|e| e.to_string())?
     else {
         eprintln!("Error: no dwarf sections found in input file");
         std::process::exit(1);
@@ -103,6 +402,21 @@ fn obfuscate_debug_info(
         .write(&mut sections)
         .map_err(|e| e.to_string())?;
 
+    // the index sections are built from the new debug info; the .debug_names strings are added to .debug_str
+    let index_sections = match options.index_sections {
+        options::IndexSections::Rebuild => {
+            // .debug_names was introduced in DWARF 5
+            let with_debug_names = has_section(elf_builder, ".debug_names")
+                && has_section(elf_builder, ".debug_str")
+                && options.dwarf_version.is_none_or(|version| version >= 5);
+            dwarf::build_index_sections(&mut sections, gimli_endian, with_debug_names)?
+        }
+        options::IndexSections::Strip => vec![],
+    };
+
+    // names of the sections that received new content
+    let mut written_sections = HashSet::<Vec<u8>>::new();
+
     // add the new debug sections to the elf file
     sections.for_each(|id, data| -> Result<(), String> {
         if !data.slice().is_empty() {
@@ -113,6 +427,15 @@ fn obfuscate_debug_info(
             {
                 sec.delete = false;
                 sec.data = SectionData::Data(data.clone().into_vec().into());
+                written_sections.insert(sec.name.to_vec());
+            } else if unsplit_dwarf.is_some() {
+                // the merged split units may need sections that the skeleton units did not use, e.g. .debug_rnglists
+                let sec = elf_builder.sections.add();
+                sec.name = id.name().as_bytes().into();
+                sec.sh_type = object::elf::SHT_PROGBITS;
+                sec.sh_addralign = 1;
+                sec.data = SectionData::Data(data.clone().into_vec().into());
+                written_sections.insert(sec.name.to_vec());
             } else {
                 return Err(format!(
                     "obfuscate is trying to add a section that does not exist in the input file: {} with {} bytes",
@@ -133,9 +456,50 @@ fn obfuscate_debug_info(
         Ok(())
     })?;
 
+    // index sections and macro sections are only replaced if they exist in the input file
+    for (name, data) in index_sections.into_iter().chain(macro_sections) {
+        if let Some(sec) = elf_builder
+            .sections
+            .iter_mut()
+            .find(|s| &*s.name == name.as_bytes())
+        {
+            sec.data = SectionData::Data(data.into());
+            written_sections.insert(sec.name.to_vec());
+        }
+    }
+
+    // any other debug section still contains the original data, which must not be passed through
+    for sec in &mut elf_builder.sections {
+        if sec.name.starts_with(b".debug") && !written_sections.contains(&*sec.name) && !sec.delete
+        {
+            report.add_warning(format!(
+                "section {} could not be rewritten and was removed from the output",
+                String::from_utf8_lossy(&sec.name)
+            ));
+            sec.delete = true;
+        }
+    }
+
     Ok(stringmapping)
 }
 
+// the names of the sections that are not deleted; the null section has an empty name and is skipped
+fn section_names(elf_builder: &object::build::elf::Builder<'_>) -> Vec<String> {
+    elf_builder
+        .sections
+        .iter()
+        .filter(|sec| !sec.name.is_empty())
+        .map(|sec| String::from_utf8_lossy(&sec.name).into_owned())
+        .collect()
+}
+
+fn has_section(elf_builder: &object::build::elf::Builder<'_>, name: &str) -> bool {
+    elf_builder
+        .sections
+        .iter()
+        .any(|sec| &*sec.name == name.as_bytes() && !sec.delete)
+}
+
 /// open a file and mmap its content
 fn load_filedata(filename: &OsStr) -> Result<memmap2::Mmap, String> {
     let file = match File::open(filename) {
@@ -178,9 +542,9 @@ fn get_file_section_reader<'data>(
     };
 
     for sec in &elf_builder.sections {
-        if &*sec.name == section_name.as_bytes() {
+        if &*sec.name == section_name.as_bytes() && !sec.delete {
             if let SectionData::Data(data) = &sec.data {
-                let input: &[u8] = &*data;
+                let input: &[u8] = data;
                 return Ok(EndianSlice::new(input, gimli_endian));
             } else {
                 return Err(format!(
@@ -0,0 +1,406 @@
+use crate::keeplist::KeepList;
+use std::ffi::OsString;
+
+/// settings for one run of the obfuscator, taken from the command line
+#[derive(Debug, Default)]
+pub(crate) struct Options {
+    pub(crate) elf_in: OsString,
+    pub(crate) elf_out: OsString,
+    pub(crate) a2l_in: OsString,
+    pub(crate) a2l_out: OsString,
+    /// .dwo files and .dwp packages that contain the split debug info of the input elf file
+    pub(crate) split_dwarf: Vec<OsString>,
+    /// only keep the variables referenced by the a2l file and the types they need
+    pub(crate) prune: bool,
+    /// move everything into one compile unit and emit identical types only once
+    pub(crate) merge_units: bool,
+    /// emit types that are identical in several compile units only once
+    pub(crate) dedup_types: bool,
+    /// keep the names of standard library entities (std::...) in the debug info
+    pub(crate) keep_std: bool,
+    /// names in the debug info and the a2l file that are not obfuscated
+    pub(crate) keep_list: KeepList,
+    /// order of the compile units, debug info entries and a2l objects in the output
+    pub(crate) order: OutputOrder,
+    /// seed for the random number generator used to shuffle the output
+    pub(crate) seed: Option<u64>,
+    /// what happens to source file names, line numbers and compiler information in the debug info
+    pub(crate) scrub_source: ScrubSource,
+    /// what happens to the constant values of variables and the default values of parameters in the debug info
+    pub(crate) const_values: ConstValues,
+    /// what happens to the name and address index sections (.debug_aranges, .debug_pubnames, ...)
+    pub(crate) index_sections: IndexSections,
+    /// what happens to the line number programs of the compile units
+    pub(crate) line_programs: LinePrograms,
+    /// compression of the debug sections in the output elf file
+    pub(crate) compress_debug: DebugCompression,
+    /// DWARF version of the output compile units; None keeps the version of each input unit
+    pub(crate) dwarf_version: Option<u16>,
+    /// address size of the output compile units; None keeps the address size of each input unit
+    pub(crate) address_size: Option<u8>,
+    /// create a symbol table for the variables in the obfuscated debug info
+    pub(crate) symbol_table: bool,
+    /// keep empty placeholders of the data sections and the loadable segments, at randomized addresses
+    pub(crate) keep_layout: bool,
+    /// what happens to the initialized data sections
+    pub(crate) calibration_data: CalibrationData,
+    /// replace the EPK (software identifier) in the a2l file and in the elf file by a generated one
+    pub(crate) replace_epk: bool,
+    /// search the output files for original identifiers
+    pub(crate) scan_leaks: LeakScan,
+    /// identifiers shorter than this are not reported by the leak scan
+    pub(crate) leak_min_length: usize,
+    /// write a report of the run as json to this file
+    pub(crate) report_file: Option<OsString>,
+    /// reload the output files and check that they are consistent with each other and with the input
+    pub(crate) verify: bool,
+}
+
+/// how source coordinates (DW_AT_decl_file, DW_AT_decl_line, ...) are handled
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ScrubSource {
+    /// copy the source coordinates unchanged
+    #[default]
+    Keep,
+    /// remove all source coordinates
+    Remove,
+    /// set all source coordinates to file 1, line 1
+    Normalize,
+}
+
+/// how DW_AT_const_value and DW_AT_default_value are handled. The values of enumerators are always kept
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ConstValues {
+    /// copy the values unchanged
+    #[default]
+    Keep,
+    /// remove the values
+    Remove,
+    /// replace the values by random values of the same size
+    Randomize,
+}
+
+/// how the name and address index sections of the debug info are handled
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum IndexSections {
+    /// build new index sections from the obfuscated debug info
+    #[default]
+    Rebuild,
+    /// remove the index sections
+    Strip,
+}
+
+/// how the line number programs (.debug_line) of the compile units are handled
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LinePrograms {
+    /// remove the line number programs
+    #[default]
+    Remove,
+    /// emit line number programs with synthetic file names and no line / address mapping
+    Synthetic,
+}
+
+/// how the debug sections of the output elf file are compressed
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum DebugCompression {
+    /// write the debug sections uncompressed
+    #[default]
+    None,
+    /// compress the debug sections with zlib (ELFCOMPRESS_ZLIB)
+    Zlib,
+    /// compress the debug sections with zstd (ELFCOMPRESS_ZSTD)
+    Zstd,
+}
+
+/// how the initialized data sections, which contain the initial values of the calibration data, are handled
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum CalibrationData {
+    /// remove the data sections
+    #[default]
+    Remove,
+    /// keep the data sections; the variables in the a2l file are set to zero
+    Zero,
+    /// keep the data sections; the variables in the a2l file get random bytes
+    Random,
+    /// keep the data sections; the variables in the a2l file get random values within their a2l limits
+    Limits,
+}
+
+/// how the objects in the output files are ordered
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum OutputOrder {
+    /// keep the order of the input files
+    #[default]
+    Original,
+    /// shuffle the objects randomly
+    Shuffle,
+    /// sort the objects by their obfuscated names
+    Sort,
+}
+
+/// what happens with the original identifiers that are found in the output files
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LeakScan {
+    /// the output is not scanned
+    #[default]
+    Off,
+    /// the identifiers are listed
+    Report,
+    /// the identifiers are listed, and the program exits with an error
+    Fail,
+}
+
+/// the default minimum length of the identifiers that are reported by the leak scan
+const DEFAULT_LEAK_MIN_LENGTH: usize = 5;
+
+pub(crate) const USAGE_OPTIONS: &str = "\
+Options:
+  --split-dwarf <FILE>    read split debug info from the .dwo file or .dwp package FILE.
+                          Can be given several times. Other split units are looked up in
+                          <input ELF>.dwp and in the .dwo files named by the skeleton units
+  --prune                 only keep the debug info of variables referenced in the a2l file
+  --merge-units           merge all compile units into a single unit
+  --dedup-types           emit types that occur in several compile units only once
+  --keep <NAME>           do not obfuscate NAME; '*' and '?' can be used as wildcards, and
+                          re:<REGEX> keeps the names that match the regular expression.
+                          Can be given several times
+  --keep-file <FILE>      do not obfuscate the names or patterns listed in FILE, one per line
+  --no-default-keep       also obfuscate the names of base types, standard typedefs (uint8_t, ...),
+                          standard macros (NULL, UINT8_MAX, ...) and AUTOSAR platform types
+                          and macros (uint8, sint16, E_OK, ...)
+  --keep-std              keep the names of standard library entities (std::...)
+  --order <ORDER>         order of the objects in the output: original (default), shuffle or sort
+  --seed <NUMBER>         seed for --order shuffle, to get a reproducible output
+  --scrub-source <MODE>   source coordinates in the debug info: keep (default), remove or normalize.
+                          Unless they are kept, compile units also get synthetic names
+  --const-values <MODE>   constant values of variables and default values of parameters in the
+                          debug info, which can contain calibration data: keep (default), remove
+                          or randomize. The values of enumerators are always kept
+  --index-sections <MODE> name and address index sections: rebuild (default) or strip
+  --line-programs <MODE>  line number programs: remove (default) or synthetic, which creates
+                          a line program without any line information for each compile unit
+  --symbol-table          create a symbol table (.symtab) for the variables in the output, using
+                          the obfuscated names and addresses
+  --keep-layout           keep the headers of the data, bss and calibration sections without
+                          their content, and the program headers of the segments that contain
+                          them. They are moved to random addresses together with their variables
+  --calibration-data <MODE>
+                          initialized data sections: remove (default), or keep them with the
+                          values of the variables in the a2l file replaced by zero, random or
+                          limits (random values within the a2l limits). All other bytes are set
+                          to zero. Implies --keep-layout
+  --replace-epk           replace the EPK (software identifier) by a generated one, in the a2l
+                          file and in the elf file, where the section that contains it is kept
+                          at its randomized address. Implies --keep-layout
+  --dwarf-version <VERSION>
+                          DWARF version (2 to 5) of the output debug info. Attributes that the
+                          version does not support are converted, or removed if there is no
+                          equivalent. By default the version of the input is kept
+  --address-size <BYTES>  address size (4 or 8) of the output debug info. By default the address
+                          size of the input is kept
+  --verify                reload the output files and check that all symbol links resolve, that
+                          the linked variables keep the types of the input, that the object
+                          names are unique and that the a2l file passes its consistency check.
+                          Violations are listed and the program exits with an error
+  --scan-leaks <MODE>     search the output files for original names of the input that were
+                          obfuscated: off (default), report, or fail, which also exits with
+                          an error if any name is found
+  --leak-min-length <LENGTH>
+                          only report names of at least LENGTH characters (default 5)
+  --report <FILE>         write a report of the run in json format to FILE: the number of renamed
+                          objects, unresolved symbol links, unreadable variables and types,
+                          removed attributes, remapped addresses, kept and deleted sections,
+                          warnings, the results of --scan-leaks and --verify, and the time of
+                          each phase
+  --compress-debug-sections <MODE>
+                          compression of the debug sections in the output: none (default),
+                          zlib or zstd";
+
+impl Options {
+    /// parse the command line arguments (excluding the program name)
+    pub(crate) fn parse(args: &[OsString]) -> Result<Self, String> {
+        let mut options = Options {
+            leak_min_length: DEFAULT_LEAK_MIN_LENGTH,
+            ..Options::default()
+        };
+        let mut positional = Vec::new();
+        let mut keep_entries = Vec::new();
+        let mut keep_files = Vec::new();
+        let mut default_keep_list = true;
+
+        let mut args_iter = args.iter();
+        while let Some(arg) = args_iter.next() {
+            match arg.to_str() {
+                Some("--split-dwarf") => {
+                    let filename = args_iter.next().ok_or("missing value for --split-dwarf")?;
+                    options.split_dwarf.push(filename.clone());
+                }
+                Some("--prune") => options.prune = true,
+                Some("--merge-units") => options.merge_units = true,
+                Some("--dedup-types") => options.dedup_types = true,
+                Some("--keep-std") => options.keep_std = true,
+                Some("--symbol-table") => options.symbol_table = true,
+                Some("--verify") => options.verify = true,
+                Some("--keep-layout") => options.keep_layout = true,
+                Some("--replace-epk") => options.replace_epk = true,
+                Some("--keep") => keep_entries.push(option_value(&mut args_iter, "--keep")?),
+                Some("--keep-file") => {
+                    keep_files.push(args_iter.next().ok_or("missing value for --keep-file")?);
+                }
+                Some("--no-default-keep") => default_keep_list = false,
+                Some("--order") => {
+                    options.order = match option_value(&mut args_iter, "--order")? {
+                        "original" => OutputOrder::Original,
+                        "shuffle" => OutputOrder::Shuffle,
+                        "sort" => OutputOrder::Sort,
+                        other => return Err(format!("invalid value {other} for --order")),
+                    };
+                }
+                Some("--scrub-source") => {
+                    options.scrub_source = match option_value(&mut args_iter, "--scrub-source")? {
+                        "keep" => ScrubSource::Keep,
+                        "remove" => ScrubSource::Remove,
+                        "normalize" => ScrubSource::Normalize,
+                        other => return Err(format!("invalid value {other} for --scrub-source")),
+                    };
+                }
+                Some("--const-values") => {
+                    options.const_values = match option_value(&mut args_iter, "--const-values")? {
+                        "keep" => ConstValues::Keep,
+                        "remove" => ConstValues::Remove,
+                        "randomize" => ConstValues::Randomize,
+                        other => return Err(format!("invalid value {other} for --const-values")),
+                    };
+                }
+                Some("--index-sections") => {
+                    options.index_sections = match option_value(&mut args_iter, "--index-sections")?
+                    {
+                        "rebuild" => IndexSections::Rebuild,
+                        "strip" => IndexSections::Strip,
+                        other => {
+                            return Err(format!("invalid value {other} for --index-sections"));
+                        }
+                    };
+                }
+                Some("--line-programs") => {
+                    options.line_programs = match option_value(&mut args_iter, "--line-programs")? {
+                        "remove" => LinePrograms::Remove,
+                        "synthetic" => LinePrograms::Synthetic,
+                        other => return Err(format!("invalid value {other} for --line-programs")),
+                    };
+                }
+                Some("--calibration-data") => {
+                    options.calibration_data =
+                        match option_value(&mut args_iter, "--calibration-data")? {
+                            "remove" => CalibrationData::Remove,
+                            "zero" => CalibrationData::Zero,
+                            "random" => CalibrationData::Random,
+                            "limits" => CalibrationData::Limits,
+                            other => {
+                                return Err(format!(
+                                    "invalid value {other} for --calibration-data"
+                                ));
+                            }
+                        };
+                }
+                Some("--scan-leaks") => {
+                    options.scan_leaks = match option_value(&mut args_iter, "--scan-leaks")? {
+                        "off" => LeakScan::Off,
+                        "report" => LeakScan::Report,
+                        "fail" => LeakScan::Fail,
+                        other => return Err(format!("invalid value {other} for --scan-leaks")),
+                    };
+                }
+                Some("--leak-min-length") => {
+                    let value = option_value(&mut args_iter, "--leak-min-length")?;
+                    options.leak_min_length = value
+                        .parse()
+                        .ok()
+                        .filter(|length| *length > 0)
+                        .ok_or_else(|| format!("invalid value {value} for --leak-min-length"))?;
+                }
+                Some("--compress-debug-sections") => {
+                    options.compress_debug =
+                        match option_value(&mut args_iter, "--compress-debug-sections")? {
+                            "none" => DebugCompression::None,
+                            "zlib" => DebugCompression::Zlib,
+                            "zstd" => DebugCompression::Zstd,
+                            other => {
+                                return Err(format!(
+                                    "invalid value {other} for --compress-debug-sections"
+                                ));
+                            }
+                        };
+                }
+                Some("--dwarf-version") => {
+                    let value = option_value(&mut args_iter, "--dwarf-version")?;
+                    let version = value
+                        .parse()
+                        .ok()
+                        .filter(|version| (2..=5).contains(version))
+                        .ok_or_else(|| format!("invalid value {value} for --dwarf-version"))?;
+                    options.dwarf_version = Some(version);
+                }
+                Some("--address-size") => {
+                    let value = option_value(&mut args_iter, "--address-size")?;
+                    let address_size =
+                        value
+                            .parse()
+                            .ok()
+                            .filter(|size| *size == 4 || *size == 8)
+                            .ok_or_else(|| format!("invalid value {value} for --address-size"))?;
+                    options.address_size = Some(address_size);
+                }
+                Some("--report") => {
+                    let filename = args_iter.next().ok_or("missing value for --report")?;
+                    options.report_file = Some(filename.clone());
+                }
+                Some("--seed") => {
+                    let value = option_value(&mut args_iter, "--seed")?;
+                    let seed = value
+                        .parse()
+                        .map_err(|_| format!("invalid value {value} for --seed"))?;
+                    options.seed = Some(seed);
+                }
+                Some(flag) if flag.starts_with("--") => {
+                    return Err(format!("unknown option {flag}"));
+                }
+                _ => positional.push(arg.clone()),
+            }
+        }
+
+        let [elf_in, elf_out, a2l_in, a2l_out] = <[OsString; 4]>::try_from(positional)
+            .map_err(|pos| format!("expected 4 file names, got {}", pos.len()))?;
+        options.elf_in = elf_in;
+        options.elf_out = elf_out;
+        options.a2l_in = a2l_in;
+        options.a2l_out = a2l_out;
+
+        options.keep_list = KeepList::new(default_keep_list);
+        for entry in keep_entries {
+            options.keep_list.add(entry)?;
+        }
+        for filename in keep_files {
+            options.keep_list.load_file(filename)?;
+        }
+        // the data can only be kept at addresses that are consistent with the debug info
+        if options.calibration_data != CalibrationData::Remove || options.replace_epk {
+            options.keep_layout = true;
+        }
+
+        Ok(options)
+    }
+}
+
+// get the value that follows an option on the command line
+fn option_value<'a>(
+    args_iter: &mut impl Iterator<Item = &'a OsString>,
+    option: &str,
+) -> Result<&'a str, String> {
+    let value = args_iter
+        .next()
+        .ok_or_else(|| format!("missing value for {option}"))?;
+    value
+        .to_str()
+        .ok_or_else(|| format!("invalid value for {option}"))
+}
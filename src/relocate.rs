@@ -0,0 +1,270 @@
+use object::build::elf::{Builder, SectionData};
+use object::{
+    Endian, Endianness, Object, ObjectSection, ObjectSymbol, RelocationKind, RelocationTarget,
+    SectionFlags, SectionIndex,
+};
+use std::collections::HashMap;
+
+// address of the first allocated section. Address 0 is avoided, because it is the
+// address of variables and functions that were discarded by the linker
+const LAYOUT_START_ADDRESS: u64 = 0x1000;
+
+/// Apply the relocations of the debug sections, if the input is a relocatable object file
+/// (a .o file, or the output of ld -r). In such a file the addresses and the offsets into other
+/// debug sections are only filled in by the linker.
+/// Afterwards the file is treated like a linked executable, so the output contains no relocations.
+/// Returns the number of relocations that could not be applied.
+pub(crate) fn apply_debug_relocations<'data>(
+    elf_builder: &mut Builder<'data>,
+    input_data: &'data [u8],
+) -> Result<usize, String> {
+    if elf_builder.header.e_type != object::elf::ET_REL {
+        return Ok(0);
+    }
+    let elffile = object::File::parse(input_data).map_err(|e| e.to_string())?;
+    let section_addresses = layout_sections(&elffile);
+
+    let mut unsupported = 0;
+    // the sections of the builder are in the same order as the sections of the file
+    for (section, builder_section) in elffile.sections().zip(elf_builder.sections.iter_mut()) {
+        if let Some(address) = section_addresses.get(&section.index()) {
+            builder_section.sh_addr = *address;
+        }
+        if !builder_section.name.starts_with(b".debug") {
+            continue;
+        }
+        if let SectionData::Data(data) = &mut builder_section.data {
+            unsupported += relocate(&elffile, &section, data.to_mut(), &section_addresses);
+        }
+    }
+
+    elf_builder.header.e_type = object::elf::ET_EXEC;
+    Ok(unsupported)
+}
+
+/// Apply the relocations of one section of a relocatable object file to the section data.
+/// Returns the number of relocations that could not be applied.
+pub(crate) fn relocate_section(
+    elffile: &object::File,
+    section: &object::Section,
+    data: &mut [u8],
+) -> usize {
+    relocate(elffile, section, data, &layout_sections(elffile))
+}
+
+// relocatable objects have no addresses yet: place the allocated sections one after the other
+fn layout_sections(elffile: &object::File) -> HashMap<SectionIndex, u64> {
+    let mut addresses = HashMap::new();
+    let mut address = LAYOUT_START_ADDRESS;
+    for section in elffile.sections() {
+        let SectionFlags::Elf { sh_flags } = section.flags() else {
+            continue;
+        };
+        if sh_flags & u64::from(object::elf::SHF_ALLOC) != 0 {
+            address = address.next_multiple_of(section.align().max(1));
+            addresses.insert(section.index(), address);
+            address += section.size();
+        }
+    }
+    addresses
+}
+
+fn relocate(
+    elffile: &object::File,
+    section: &object::Section,
+    data: &mut [u8],
+    section_addresses: &HashMap<SectionIndex, u64>,
+) -> usize {
+    let endian = elffile.endianness();
+    let mut unsupported = 0;
+    for (offset, relocation) in section.relocations() {
+        // only absolute relocations occur in debug info; everything else, e.g. the RISC-V
+        // ADD / SUB pairs for address differences or TLS offsets, is left unchanged
+        let target = match relocation.target() {
+            RelocationTarget::Symbol(index) => elffile.symbol_by_index(index).ok().map(|symbol| {
+                let base = symbol
+                    .section_index()
+                    .and_then(|index| section_addresses.get(&index))
+                    .copied()
+                    .unwrap_or(0);
+                base + symbol.address()
+            }),
+            RelocationTarget::Section(index) => {
+                Some(section_addresses.get(&index).copied().unwrap_or(0))
+            }
+            RelocationTarget::Absolute => Some(0),
+            _ => None,
+        };
+        let size = usize::from(relocation.size() / 8);
+        let range = usize::try_from(offset)
+            .ok()
+            .and_then(|start| data.get_mut(start..start.checked_add(size)?));
+        let (Some(target), Some(bytes), RelocationKind::Absolute) =
+            (target, range, relocation.kind())
+        else {
+            unsupported += 1;
+            continue;
+        };
+        if !matches!(size, 2 | 4 | 8) {
+            unsupported += 1;
+            continue;
+        }
+
+        let value = if relocation.has_implicit_addend() {
+            target.wrapping_add(read_value(bytes, endian))
+        } else {
+            target.wrapping_add_signed(relocation.addend())
+        };
+        write_value(bytes, value, endian);
+    }
+    unsupported
+}
+
+fn read_value(bytes: &[u8], endian: Endianness) -> u64 {
+    match bytes.len() {
+        2 => endian.read_u16([bytes[0], bytes[1]]).into(),
+        4 => endian
+            .read_u32([bytes[0], bytes[1], bytes[2], bytes[3]])
+            .into(),
+        _ => endian.read_u64(bytes.try_into().unwrap_or_default()),
+    }
+}
+
+// write the value, truncated to the size of the relocated field
+fn write_value(bytes: &mut [u8], value: u64, endian: Endianness) {
+    match bytes.len() {
+        2 => bytes.copy_from_slice(&endian.write_u16(value as u16)),
+        4 => bytes.copy_from_slice(&endian.write_u32(value as u32)),
+        _ => bytes.copy_from_slice(&endian.write_u64(value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use object::write::{self, Relocation, StandardSection, Symbol, SymbolSection};
+    use object::{Architecture, BinaryFormat, RelocationEncoding, RelocationFlags, SymbolFlags};
+
+    // .text at 0x1000, .data at 0x1030, with the variable at offset 4 in .data
+    const TEXT_ADDRESS: u64 = 0x1000;
+    const VAR_ADDRESS: u64 = 0x1034;
+
+    // a relocatable object with a .text and a .data section, and a .debug_info section with the given relocations
+    fn relocatable_object(
+        architecture: Architecture,
+        relocations: &[(u64, RelocationKind, u8, bool, i64)],
+    ) -> Vec<u8> {
+        let mut obj = write::Object::new(BinaryFormat::Elf, architecture, Endianness::Little);
+        let text = obj.section_id(StandardSection::Text);
+        obj.append_section_data(text, &[0x90; 0x30], 16);
+        let data = obj.section_id(StandardSection::Data);
+        obj.append_section_data(data, &[0; 0x10], 8);
+        let var = obj.add_symbol(Symbol {
+            name: b"var".to_vec(),
+            value: 4,
+            size: 4,
+            kind: object::SymbolKind::Data,
+            scope: object::SymbolScope::Compilation,
+            weak: false,
+            section: SymbolSection::Section(data),
+            flags: SymbolFlags::None,
+        });
+        let text_symbol = obj.section_symbol(text);
+        let debug_info =
+            obj.add_section(vec![], b".debug_info".to_vec(), object::SectionKind::Debug);
+        obj.append_section_data(debug_info, &[0; 32], 1);
+        for (offset, kind, size, to_text, addend) in relocations {
+            let relocation = Relocation {
+                offset: *offset,
+                symbol: if *to_text { text_symbol } else { var },
+                addend: *addend,
+                flags: RelocationFlags::Generic {
+                    kind: *kind,
+                    encoding: RelocationEncoding::Generic,
+                    size: *size,
+                },
+            };
+            obj.add_relocation(debug_info, relocation).unwrap();
+        }
+        obj.write().unwrap()
+    }
+
+    // apply the relocations, and return the relocated .debug_info and the addresses of .text and .data
+    fn apply(input: &[u8]) -> (Vec<u8>, usize, u64, u64) {
+        let mut elf_builder = Builder::read(input).unwrap();
+        let unsupported = apply_debug_relocations(&mut elf_builder, input).unwrap();
+        assert_eq!(elf_builder.header.e_type, object::elf::ET_EXEC);
+        let section = |name: &[u8]| {
+            elf_builder
+                .sections
+                .iter()
+                .find(|section| section.name.as_slice() == name)
+                .unwrap()
+        };
+        let SectionData::Data(debug_info) = &section(b".debug_info").data else {
+            panic!(".debug_info has no data");
+        };
+        (
+            debug_info.to_vec(),
+            unsupported,
+            section(b".text").sh_addr,
+            section(b".data").sh_addr,
+        )
+    }
+
+    #[test]
+    fn explicit_addends() {
+        // x86-64 uses SHT_RELA
+        let input = relocatable_object(
+            Architecture::X86_64,
+            &[
+                (0, RelocationKind::Absolute, 64, false, 2),
+                (8, RelocationKind::Absolute, 32, false, 0),
+                (12, RelocationKind::Absolute, 16, true, 0x10),
+                // PC-relative relocations don't occur in debug info, and are not applied
+                (16, RelocationKind::Relative, 32, false, 0),
+            ],
+        );
+        let (debug_info, unsupported, text_address, data_address) = apply(&input);
+        assert_eq!((text_address, data_address), (TEXT_ADDRESS, 0x1030));
+        assert_eq!(unsupported, 1);
+        assert_eq!(debug_info[0..8], (VAR_ADDRESS + 2).to_le_bytes());
+        assert_eq!(debug_info[8..12], (VAR_ADDRESS as u32).to_le_bytes());
+        assert_eq!(
+            debug_info[12..14],
+            (TEXT_ADDRESS as u16 + 0x10).to_le_bytes()
+        );
+        assert_eq!(debug_info[16..20], [0; 4]);
+    }
+
+    #[test]
+    fn implicit_addends() {
+        // i386 uses SHT_REL: the addend is stored in the relocated field.
+        // The object writer only supports 32-bit fields with an addend
+        let input = relocatable_object(
+            Architecture::I386,
+            &[
+                (0, RelocationKind::Absolute, 32, false, 6),
+                (4, RelocationKind::Absolute, 16, true, 0),
+            ],
+        );
+        let (debug_info, unsupported, ..) = apply(&input);
+        assert_eq!(unsupported, 0);
+        assert_eq!(debug_info[0..4], (VAR_ADDRESS as u32 + 6).to_le_bytes());
+        assert_eq!(debug_info[4..6], (TEXT_ADDRESS as u16).to_le_bytes());
+    }
+
+    #[test]
+    fn read_and_write_values() {
+        let mut bytes = [0u8; 8];
+        write_value(&mut bytes[0..2], 0x1_2345, Endianness::Big);
+        assert_eq!(bytes[0..2], [0x23, 0x45]);
+        assert_eq!(read_value(&bytes[0..2], Endianness::Big), 0x2345);
+        write_value(&mut bytes[0..4], 0x1_2345_6789, Endianness::Little);
+        assert_eq!(bytes[0..4], [0x89, 0x67, 0x45, 0x23]);
+        assert_eq!(read_value(&bytes[0..4], Endianness::Little), 0x2345_6789);
+        write_value(&mut bytes, 0x0102_0304_0506_0708, Endianness::Big);
+        assert_eq!(bytes, [1, 2, 3, 4, 5, 6, 7, 8]);
+        assert_eq!(read_value(&bytes, Endianness::Big), 0x0102_0304_0506_0708);
+    }
+}
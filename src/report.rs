@@ -0,0 +1,309 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::ffi::OsStr;
+use std::fmt::Write;
+use std::time::Duration;
+
+/// the events of one run of the obfuscator. They are printed as a short summary at the end
+/// of the run, and can be written to a json file
+#[derive(Debug, Default)]
+pub(crate) struct Report {
+    /// the seed of the random number generator, if the output was shuffled without a given seed
+    pub(crate) shuffle_seed: Option<u64>,
+    /// number of renamed a2l objects for each object type, e.g. CHARACTERISTIC
+    renamed_objects: BTreeMap<&'static str, usize>,
+    /// number of distinct names in the debug info that were replaced
+    pub(crate) renamed_debug_names: usize,
+    /// symbol links of the a2l file that could not be resolved: the symbol name and the error
+    unresolved_symbols: Vec<(String, String)>,
+    /// the same links as (name of the object, symbol name) in the output a2l file, so that
+    /// --verify does not report them again
+    unresolved_links: HashSet<(String, String)>,
+    /// variables and types that could not be read from the debug info. The same file can be
+    /// read several times, so each error is only listed once
+    debug_info_errors: BTreeSet<String>,
+    dropped_attributes: BTreeMap<String, usize>,
+    dropped_forms: BTreeMap<&'static str, usize>,
+    remapped_addresses: usize,
+    sections_kept: Vec<String>,
+    sections_deleted: Vec<String>,
+    pub(crate) warnings: Vec<String>,
+    /// results of --scan-leaks and --verify, if they were run
+    pub(crate) leaks: Option<Vec<String>>,
+    pub(crate) violations: Option<Vec<String>>,
+    timing: Vec<(&'static str, Duration)>,
+}
+
+impl Report {
+    pub(crate) fn add_renamed_object(&mut self, kind: &'static str) {
+        *self.renamed_objects.entry(kind).or_default() += 1;
+    }
+
+    pub(crate) fn add_unresolved_symbol(&mut self, symbol_name: &str, error: String) {
+        self.unresolved_symbols
+            .push((symbol_name.to_string(), error));
+    }
+
+    pub(crate) fn add_debug_info_errors(&mut self, errors: &[String]) {
+        self.debug_info_errors.extend(errors.iter().cloned());
+    }
+
+    /// an attribute that was dropped, because the meaning of the (vendor) attribute is unknown
+    pub(crate) fn add_dropped_attribute(&mut self, name: String) {
+        *self.dropped_attributes.entry(name).or_default() += 1;
+    }
+
+    /// an attribute that was dropped, because its form is not supported
+    pub(crate) fn add_dropped_form(&mut self, form: &'static str) {
+        *self.dropped_forms.entry(form).or_default() += 1;
+    }
+
+    /// a link of an object in the output a2l file that was written although it could not be resolved
+    pub(crate) fn add_unresolved_link(&mut self, object_name: &str, symbol_name: &str) {
+        self.unresolved_links
+            .insert((object_name.to_string(), symbol_name.to_string()));
+    }
+
+    pub(crate) fn is_unresolved_link(&self, object_name: &str, symbol_name: &str) -> bool {
+        self.unresolved_links
+            .contains(&(object_name.to_string(), symbol_name.to_string()))
+    }
+
+    pub(crate) fn add_remapped_address(&mut self) {
+        self.remapped_addresses += 1;
+    }
+
+    /// record the sections of the output; all other sections of the input were deleted
+    pub(crate) fn set_sections(
+        &mut self,
+        input_sections: Vec<String>,
+        output_sections: Vec<String>,
+    ) {
+        let mut deleted = input_sections;
+        for name in &output_sections {
+            if let Some(pos) = deleted.iter().position(|input_name| input_name == name) {
+                deleted.remove(pos);
+            }
+        }
+        self.sections_kept = output_sections;
+        self.sections_deleted = deleted;
+    }
+
+    pub(crate) fn add_warning(&mut self, warning: String) {
+        self.warnings.push(warning);
+    }
+
+    pub(crate) fn add_timing(&mut self, phase: &'static str, duration: Duration) {
+        self.timing.push((phase, duration));
+    }
+
+    /// print a short summary to stdout
+    pub(crate) fn print_summary(&self) {
+        if let Some(seed) = self.shuffle_seed {
+            println!("Shuffled the output with seed {seed}");
+        }
+        for warning in &self.warnings {
+            println!("Warning: {warning}");
+        }
+        if !self.debug_info_errors.is_empty() {
+            println!(
+                "{} variables or types could not be read from the debug info:",
+                self.debug_info_errors.len()
+            );
+            for error in &self.debug_info_errors {
+                println!("  {error}");
+            }
+        }
+        if !self.dropped_attributes.is_empty() {
+            println!(
+                "Removed vendor attributes with unknown meaning: {}",
+                count_list(&self.dropped_attributes)
+            );
+        }
+        if !self.dropped_forms.is_empty() {
+            println!(
+                "Removed attributes with unsupported forms: {}",
+                count_list(&self.dropped_forms)
+            );
+        }
+        if !self.unresolved_symbols.is_empty() {
+            println!(
+                "{} symbol links could not be resolved:",
+                self.unresolved_symbols.len()
+            );
+            for (_, error) in &self.unresolved_symbols {
+                println!("  {error}");
+            }
+        }
+        if !self.renamed_objects.is_empty() {
+            println!("Renamed a2l objects: {}", count_list(&self.renamed_objects));
+        }
+        println!(
+            "Renamed {} names and remapped {} addresses in the debug info",
+            self.renamed_debug_names, self.remapped_addresses
+        );
+        println!(
+            "Kept {} sections, deleted {} sections",
+            self.sections_kept.len(),
+            self.sections_deleted.len()
+        );
+        match &self.leaks {
+            Some(leaks) if leaks.is_empty() => println!("No original names found in the output"),
+            Some(leaks) => {
+                println!("Original names found in the output:");
+                for leak in leaks {
+                    println!("  {leak}");
+                }
+            }
+            None => {}
+        }
+        match &self.violations {
+            Some(violations) if violations.is_empty() => {
+                println!("Verification of the output passed");
+            }
+            Some(violations) => {
+                println!("Verification of the output failed:");
+                for violation in violations {
+                    println!("  {violation}");
+                }
+            }
+            None => {}
+        }
+        let phases: Vec<_> = self
+            .timing
+            .iter()
+            .map(|(phase, duration)| format!("{phase} {:.2}s", duration.as_secs_f64()))
+            .collect();
+        println!("Time: {}", phases.join(", "));
+    }
+
+    /// write the report as a json file
+    pub(crate) fn write_json(&self, filename: &OsStr) -> Result<(), String> {
+        let fields = [
+            (
+                "shuffle_seed",
+                self.shuffle_seed
+                    .map_or("null".to_string(), |seed| seed.to_string()),
+            ),
+            ("renamed_objects", json_counts(&self.renamed_objects)),
+            ("renamed_debug_names", self.renamed_debug_names.to_string()),
+            (
+                "unresolved_symbol_links",
+                json_array(self.unresolved_symbols.iter().map(|(symbol, error)| {
+                    format!(
+                        "{{\"symbol\": {}, \"error\": {}}}",
+                        json_string(symbol),
+                        json_string(error)
+                    )
+                })),
+            ),
+            (
+                "debug_info_errors",
+                json_array(
+                    self.debug_info_errors
+                        .iter()
+                        .map(|error| json_string(error)),
+                ),
+            ),
+            ("dropped_attributes", json_counts(&self.dropped_attributes)),
+            ("dropped_forms", json_counts(&self.dropped_forms)),
+            ("remapped_addresses", self.remapped_addresses.to_string()),
+            (
+                "sections",
+                format!(
+                    "{{\"kept\": {}, \"deleted\": {}}}",
+                    json_strings(&self.sections_kept),
+                    json_strings(&self.sections_deleted)
+                ),
+            ),
+            ("warnings", json_strings(&self.warnings)),
+            (
+                "leaks",
+                self.leaks
+                    .as_ref()
+                    .map_or("null".to_string(), |leaks| json_strings(leaks)),
+            ),
+            (
+                "verification",
+                self.violations
+                    .as_ref()
+                    .map_or("null".to_string(), |violations| {
+                        format!(
+                            "{{\"passed\": {}, \"violations\": {}}}",
+                            violations.is_empty(),
+                            json_strings(violations)
+                        )
+                    }),
+            ),
+            (
+                "timing_seconds",
+                format!(
+                    "{{{}}}",
+                    self.timing
+                        .iter()
+                        .map(|(phase, duration)| format!(
+                            "{}: {:.3}",
+                            json_string(phase),
+                            duration.as_secs_f64()
+                        ))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ),
+            ),
+        ];
+
+        let mut json = String::from("{\n");
+        for (idx, (name, value)) in fields.iter().enumerate() {
+            let separator = if idx + 1 < fields.len() { "," } else { "" };
+            let _ = writeln!(json, "  {}: {value}{separator}", json_string(name));
+        }
+        json.push_str("}\n");
+
+        std::fs::write(filename, json)
+            .map_err(|e| format!("could not write {}: {e}", filename.to_string_lossy()))
+    }
+}
+
+// "name (count), ..."
+fn count_list<K: std::fmt::Display>(counts: &BTreeMap<K, usize>) -> String {
+    counts
+        .iter()
+        .map(|(name, count)| format!("{name} ({count})"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn json_counts<K: AsRef<str>>(counts: &BTreeMap<K, usize>) -> String {
+    let items: Vec<_> = counts
+        .iter()
+        .map(|(name, count)| format!("{}: {count}", json_string(name.as_ref())))
+        .collect();
+    format!("{{{}}}", items.join(", "))
+}
+
+fn json_strings(items: &[String]) -> String {
+    json_array(items.iter().map(|item| json_string(item)))
+}
+
+fn json_array(items: impl Iterator<Item = String>) -> String {
+    format!("[{}]", items.collect::<Vec<_>>().join(", "))
+}
+
+fn json_string(text: &str) -> String {
+    let mut output = String::with_capacity(text.len() + 2);
+    output.push('"');
+    for c in text.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if c.is_control() => {
+                let _ = write!(output, "\\u{:04x}", u32::from(c));
+            }
+            c => output.push(c),
+        }
+    }
+    output.push('"');
+    output
+}
@@ -0,0 +1,323 @@
+use crate::SliceType;
+use gimli::write::{Address, ConvertResult, ConvertUnit, ConvertUnitEntry, EndianVec, Sections};
+use gimli::{DwoId, EndianSlice, Reader, RunTimeEndian, Section, SectionId};
+use object::{Object, ObjectSection};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::path::{Path, PathBuf};
+
+// the uncompressed sections of a .dwo file or a .dwp package, by name
+type SplitSections<'data> = HashMap<String, Cow<'data, [u8]>>;
+
+/// debug sections in which the split units from .dwo files and .dwp packages have been merged
+/// with their skeleton units
+pub(crate) struct UnsplitDwarf {
+    sections: HashMap<SectionId, Vec<u8>>,
+    endian: RunTimeEndian,
+    /// the names of the split units that were not found; only their skeleton units are used
+    pub(crate) missing_units: Vec<String>,
+}
+
+impl UnsplitDwarf {
+    /// the merged debug info, which only contains ordinary compile units
+    pub(crate) fn dwarf(&self) -> gimli::Dwarf<SliceType<'_>> {
+        let loader = |id: SectionId| -> Result<SliceType<'_>, std::convert::Infallible> {
+            let data = self.sections.get(&id).map_or(&[][..], Vec::as_slice);
+            Ok(EndianSlice::new(data, self.endian))
+        };
+        let Ok(dwarf) = gimli::Dwarf::load(loader);
+        dwarf
+    }
+}
+
+/// Merge the split units from .dwo files and .dwp packages into their skeleton units, so that the
+/// result can be processed like the debug info of a program that was built without -gsplit-dwarf.
+/// The split units are searched in split_files first, then in <elf file>.dwp and finally in the
+/// .dwo files named by the skeleton units.
+/// Returns None if the debug info does not contain any skeleton units.
+pub(crate) fn unsplit_dwarf(
+    dwarf: &gimli::Dwarf<SliceType>,
+    elf_filename: &OsStr,
+    split_files: &[OsString],
+) -> Result<Option<UnsplitDwarf>, String> {
+    let skeletons = skeleton_units(dwarf);
+    if skeletons.is_empty() {
+        return Ok(None);
+    }
+
+    let mut filenames: Vec<PathBuf> = split_files.iter().map(PathBuf::from).collect();
+    let mut dwp_filename = elf_filename.to_os_string();
+    dwp_filename.push(".dwp");
+    let elf_dir = Path::new(elf_filename).parent().unwrap_or(Path::new(""));
+    let auto_filenames = std::iter::once(PathBuf::from(dwp_filename)).chain(
+        skeletons
+            .iter()
+            .filter_map(|(_, dwo_path)| find_dwo_file(dwo_path.as_deref()?, elf_dir)),
+    );
+    for filename in auto_filenames {
+        if filename.is_file() && !filenames.contains(&filename) {
+            filenames.push(filename);
+        }
+    }
+
+    // the file data must outlive the split units that reference it
+    let filedata = filenames
+        .iter()
+        .map(|filename| crate::load_filedata(filename.as_os_str()))
+        .collect::<Result<Vec<_>, String>>()?;
+    let split_sections = filenames
+        .iter()
+        .zip(&filedata)
+        .map(|(filename, data)| read_split_sections(filename, data))
+        .collect::<Result<Vec<_>, String>>()?;
+    let mut split_units = HashMap::new();
+    for (filename, (sections, endian)) in filenames.iter().zip(&split_sections) {
+        load_split_units(
+            filename,
+            sections,
+            *endian,
+            dwarf,
+            &skeletons,
+            &mut split_units,
+        )?;
+    }
+
+    let missing_units = skeletons
+        .iter()
+        .filter(|(dwo_id, _)| !split_units.contains_key(dwo_id))
+        .map(|(dwo_id, dwo_path)| {
+            dwo_path.as_ref().map_or_else(
+                || format!("{:#x}", dwo_id.0),
+                |path| path.display().to_string(),
+            )
+        })
+        .collect();
+
+    let mut write_dwarf = gimli::write::Dwarf::new();
+    convert_units(dwarf, &split_units, &mut write_dwarf)
+        .map_err(|err| format!("failed to merge the split debug info: {err}"))?;
+
+    let endian = dwarf.debug_info.reader().endian();
+    let mut sections = Sections::new(EndianVec::new(endian));
+    write_dwarf
+        .write(&mut sections)
+        .map_err(|e| e.to_string())?;
+    let mut unsplit = UnsplitDwarf {
+        sections: HashMap::new(),
+        endian,
+        missing_units,
+    };
+    sections.for_each(|id, data| -> Result<(), String> {
+        unsplit.sections.insert(id, data.slice().to_vec());
+        Ok(())
+    })?;
+    // the macro info of units that were not split is referenced with unchanged offsets
+    unsplit.sections.insert(
+        SectionId::DebugMacro,
+        dwarf.debug_macro.reader().slice().to_vec(),
+    );
+    unsplit.sections.insert(
+        SectionId::DebugMacinfo,
+        dwarf.debug_macinfo.reader().slice().to_vec(),
+    );
+
+    Ok(Some(unsplit))
+}
+
+// get the dwo id and the .dwo file name of all skeleton units
+fn skeleton_units(dwarf: &gimli::Dwarf<SliceType>) -> Vec<(DwoId, Option<PathBuf>)> {
+    let mut skeletons = vec![];
+    let mut units = dwarf.units();
+    while let Ok(Some(header)) = units.next() {
+        let Ok(unit) = dwarf.unit(header) else {
+            continue;
+        };
+        let Some(dwo_id) = unit.dwo_id else {
+            continue;
+        };
+        // the dwo name is relative to the compilation directory
+        let dwo_path = unit
+            .dwo_name()
+            .ok()
+            .flatten()
+            .and_then(|value| dwarf.attr_string(&unit, value).ok())
+            .map(|dwo_name| {
+                let comp_dir = unit
+                    .comp_dir
+                    .map(|dir| dir.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                Path::new(&comp_dir).join(&*dwo_name.to_string_lossy())
+            });
+        skeletons.push((dwo_id, dwo_path));
+    }
+    skeletons
+}
+
+// the .dwo file might have been moved together with the elf file, after the build
+fn find_dwo_file(dwo_path: &Path, elf_dir: &Path) -> Option<PathBuf> {
+    if dwo_path.is_file() {
+        Some(dwo_path.to_path_buf())
+    } else {
+        Some(elf_dir.join(dwo_path.file_name()?))
+    }
+}
+
+// read the uncompressed debug sections of a .dwo file or a .dwp package
+fn read_split_sections<'data>(
+    filename: &Path,
+    filedata: &'data [u8],
+) -> Result<(SplitSections<'data>, RunTimeEndian), String> {
+    let file = object::File::parse(filedata).map_err(|err| {
+        format!(
+            "Error: Failed to parse file '{}': {err}",
+            filename.display()
+        )
+    })?;
+    let endian = if file.is_little_endian() {
+        RunTimeEndian::Little
+    } else {
+        RunTimeEndian::Big
+    };
+    let mut sections = HashMap::new();
+    for section in file.sections() {
+        let Ok(name) = section.name() else {
+            continue;
+        };
+        let name = match name.strip_prefix(".zdebug") {
+            Some(suffix) => format!(".debug{suffix}"),
+            None => name.to_string(),
+        };
+        let data = section
+            .uncompressed_data()
+            .map_err(|err| format!("failed to decompress section {name}: {err}"))?;
+        sections.insert(name, data);
+    }
+    Ok((sections, endian))
+}
+
+// load the split units that belong to the skeleton units from a .dwo file or a .dwp package.
+// Units that were found in an earlier file are not replaced
+fn load_split_units<'data>(
+    filename: &Path,
+    sections: &'data SplitSections<'data>,
+    endian: RunTimeEndian,
+    dwarf: &gimli::Dwarf<SliceType<'data>>,
+    skeletons: &[(DwoId, Option<PathBuf>)],
+    split_units: &mut HashMap<DwoId, gimli::Dwarf<SliceType<'data>>>,
+) -> Result<(), String> {
+    // the sections in .dwo and .dwp files are named .debug_<xyz>.dwo
+    let loader = |id: SectionId| -> Result<SliceType<'data>, gimli::Error> {
+        let data = id
+            .dwo_name()
+            .and_then(|name| sections.get(name))
+            .map_or(&[][..], |data| &**data);
+        Ok(EndianSlice::new(data, endian))
+    };
+    let load_error = |err: gimli::Error| format!("failed to load {}: {err}", filename.display());
+
+    if sections.contains_key(".debug_cu_index") {
+        let package =
+            gimli::DwarfPackage::load(loader, EndianSlice::new(&[], endian)).map_err(load_error)?;
+        for (dwo_id, _) in skeletons {
+            if !split_units.contains_key(dwo_id)
+                && let Some(split_dwarf) = package.find_cu(*dwo_id, dwarf).map_err(load_error)?
+            {
+                split_units.insert(*dwo_id, split_dwarf);
+            }
+        }
+    } else {
+        let mut split_dwarf = gimli::Dwarf::load(loader).map_err(load_error)?;
+        split_dwarf.make_dwo(dwarf);
+        let header = split_dwarf.units().next().map_err(load_error)?;
+        if let Some(header) = header
+            && let Some(dwo_id) = split_dwarf.unit(header).map_err(load_error)?.dwo_id
+        {
+            split_units.entry(dwo_id).or_insert(split_dwarf);
+        }
+    }
+    Ok(())
+}
+
+// convert all units of the elf file; skeleton units are replaced by the content of their split unit
+fn convert_units<'data>(
+    dwarf: &gimli::Dwarf<SliceType<'data>>,
+    split_units: &HashMap<DwoId, gimli::Dwarf<SliceType<'data>>>,
+    write_dwarf: &mut gimli::write::Dwarf,
+) -> ConvertResult<()> {
+    let mut convert = write_dwarf.convert(dwarf)?;
+    while let Some((mut unit, root_entry)) = convert.read_unit()? {
+        let split_dwarf = unit
+            .read_unit
+            .dwo_id
+            .and_then(|dwo_id| split_units.get(&dwo_id));
+        if let Some(split_dwarf) = split_dwarf {
+            // the skeleton unit has the attributes that refer to the elf file, e.g. DW_AT_low_pc
+            let root_id = unit.unit.root();
+            convert_attributes(&mut unit, root_id, &root_entry)?;
+            let mut convert_split = unit.convert_split(split_dwarf)?;
+            let (mut split_unit, split_root_entry) = convert_split.read_unit()?;
+            convert_entries(&mut split_unit, split_root_entry)?;
+        } else {
+            convert_entries(&mut unit, root_entry)?;
+        }
+    }
+    Ok(())
+}
+
+fn convert_entries<'a, 'data>(
+    unit: &mut ConvertUnit<'a, SliceType<'data>>,
+    root_entry: ConvertUnitEntry<'a, SliceType<'data>>,
+) -> ConvertResult<()> {
+    if let Some(convert_program) = unit.read_line_program(None, None)? {
+        let (program, files) = convert_program.convert(&convert_address)?;
+        unit.set_line_program(program, files);
+    }
+    let root_id = unit.unit.root();
+    convert_attributes(unit, root_id, &root_entry)?;
+
+    let mut entry = root_entry;
+    while let Some(id) = unit.read_entry(&mut entry)? {
+        let id = unit.add_entry(id, &entry);
+        convert_attributes(unit, id, &entry)?;
+    }
+    Ok(())
+}
+
+fn convert_attributes<'data>(
+    unit: &mut ConvertUnit<'_, SliceType<'data>>,
+    id: gimli::write::UnitEntryId,
+    entry: &ConvertUnitEntry<'_, SliceType<'data>>,
+) -> ConvertResult<()> {
+    for attr in entry.attrs() {
+        // the macro info of a split unit is stored in the .dwo file, and it is not merged
+        if unit.read_skeleton_unit.is_some()
+            && matches!(
+                attr.name(),
+                gimli::constants::DW_AT_macros
+                    | gimli::constants::DW_AT_GNU_macros
+                    | gimli::constants::DW_AT_macro_info
+            )
+        {
+            continue;
+        }
+        let value = match attr.value() {
+            gimli::AttributeValue::SecOffset(offset)
+                if attr.name() == gimli::constants::DW_AT_GNU_macros =>
+            {
+                gimli::write::AttributeValue::DebugMacroRef(gimli::DebugMacroOffset(offset))
+            }
+            // other section offsets belong to vendor attributes like DW_AT_GNU_locviews, which
+            // can't be converted. The obfuscation drops them anyway
+            gimli::AttributeValue::SecOffset(_) => continue,
+            _ => unit.convert_attribute_value(entry.read_unit, attr, &convert_address)?,
+        };
+        unit.unit.get_mut(id).set(attr.name(), value);
+    }
+    Ok(())
+}
+
+// addresses are copied unchanged
+fn convert_address(address: u64) -> Option<Address> {
+    Some(Address::Constant(address))
+}
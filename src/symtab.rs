@@ -0,0 +1,91 @@
+use crate::a2l::debuginfo::{DebugData, TypeInfo};
+use crate::report::Report;
+use object::build::elf::{Builder, SectionData, SectionId};
+
+/// Create a symbol table that contains a symbol for each variable in the obfuscated debug info.
+/// The symbols use the obfuscated names and addresses, and the sizes of the variable types,
+/// so that tools which resolve names through the symbol table find the same variables as in the debug info.
+pub(crate) fn rebuild_symbol_table(
+    elf_builder: &mut Builder<'_>,
+    report: &mut Report,
+) -> Result<(), String> {
+    let dwarf = crate::load_dwarf_sections(elf_builder)?;
+    let debug_data = DebugData::load_dwarf_from(dwarf, elf_builder.endian);
+    report.add_debug_info_errors(&debug_data.load_errors);
+
+    let mut symbols = vec![];
+    for (name, varinfos) in &debug_data.variables {
+        for varinfo in varinfos {
+            // only variables with DW_AT_external are visible outside of their compile unit
+            let binding = if varinfo.external {
+                object::elf::STB_GLOBAL
+            } else {
+                object::elf::STB_LOCAL
+            };
+            let size = debug_data
+                .types
+                .get(&varinfo.typeref)
+                .map_or(0, TypeInfo::get_size);
+            symbols.push((binding, name.clone(), varinfo.address, size));
+        }
+    }
+    // all local symbols must come before the global symbols
+    symbols.sort_by_key(|(binding, ..)| *binding != object::elf::STB_LOCAL);
+
+    let placeholder_sections: Vec<_> = elf_builder
+        .sections
+        .iter()
+        .filter(|sec| !sec.delete && sec.is_alloc())
+        .map(|sec| (sec.sh_addr, sec.sh_size, sec.id()))
+        .collect();
+
+    let strtab_id = add_symbol_table_section(
+        elf_builder,
+        ".strtab",
+        object::elf::SHT_STRTAB,
+        SectionData::String,
+    );
+    let symtab_id = add_symbol_table_section(
+        elf_builder,
+        ".symtab",
+        object::elf::SHT_SYMTAB,
+        SectionData::Symbol,
+    );
+    let symtab = elf_builder.sections.get_mut(symtab_id);
+    symtab.sh_link_section = Some(strtab_id);
+    symtab.sh_addralign = if elf_builder.is_64 { 8 } else { 4 };
+
+    for (binding, name, address, size) in symbols {
+        let symbol = elf_builder.symbols.add();
+        symbol.name = name.into_bytes().into();
+        symbol.set_st_info(binding, object::elf::STT_OBJECT);
+        // usually the sections that contained the variables are not part of the output,
+        // unless they were kept as placeholders
+        symbol.section = placeholder_sections
+            .iter()
+            .find(|(start, size, _)| address >= *start && address - *start < *size)
+            .map(|(_, _, id)| *id);
+        if symbol.section.is_none() {
+            symbol.st_shndx = object::elf::SHN_ABS;
+        }
+        symbol.st_value = address;
+        symbol.st_size = size;
+    }
+    Ok(())
+}
+
+// add a new symbol table or string table section. The sections of the input file were deleted
+// with all other non-debug sections, and deleted sections can't be found in the section table any more
+fn add_symbol_table_section(
+    elf_builder: &mut Builder<'_>,
+    name: &'static str,
+    sh_type: u32,
+    data: SectionData<'static>,
+) -> SectionId {
+    let sec = elf_builder.sections.add();
+    sec.name = name.as_bytes().into();
+    sec.sh_type = sh_type;
+    sec.sh_addralign = 1;
+    sec.data = data;
+    sec.id()
+}